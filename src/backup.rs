@@ -0,0 +1,124 @@
+//! Timestamped copies of the bookmarks database, kept in `markd/backups` in the
+//! data directory and named after the file they were taken from.
+
+use crate::{
+    config::Config,
+    db::{self, data_home, Access, Database},
+    new_table, print_table,
+};
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDateTime};
+use colored::Colorize;
+use std::{
+    ffi::OsStr,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+const TIMESTAMP: &str = "%Y%m%d-%H%M%S-%3f";
+
+pub fn dir() -> PathBuf {
+    data_home().join("backups")
+}
+
+/// Name and extension of the backed up file, so profiles and formats get their own backups
+fn stem_and_ext(db: &Database) -> (String, String) {
+    let file = db.file().unwrap_or(db::path());
+    let part = |part: Option<&OsStr>| part.map(|p| p.to_string_lossy().to_string());
+    let stem = part(file.file_stem()).unwrap_or_else(|| "bookmarks".into());
+    let ext = part(file.extension()).unwrap_or_else(|| "toml".into());
+    (stem, ext)
+}
+
+/// Backups of the database, oldest first
+pub fn backups(db: &Database) -> Result<Vec<PathBuf>> {
+    let (stem, ext) = stem_and_ext(db);
+    let entries = match std::fs::read_dir(dir()) {
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        entries => entries.with_context(|| format!("failed to read {}", dir().display()))?,
+    };
+    let mut backups: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| taken_at(path, &stem, &ext).is_some())
+        .collect();
+    // Timestamps sort chronologically as text
+    backups.sort();
+    Ok(backups)
+}
+
+fn taken_at(path: &Path, stem: &str, ext: &str) -> Option<NaiveDateTime> {
+    let name = path.file_name()?.to_str()?;
+    let timestamp = name.strip_prefix(stem)?.strip_prefix('-')?;
+    let timestamp = timestamp.strip_suffix(ext)?.strip_suffix('.')?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP).ok()
+}
+
+/// Writes a backup and deletes the oldest ones beyond `keep`
+pub fn create(db: &Database, keep: usize) -> Result<PathBuf> {
+    let (stem, ext) = stem_and_ext(db);
+    let timestamp = Local::now().format(TIMESTAMP);
+    let path = dir().join(format!("{stem}-{timestamp}.{ext}"));
+    std::fs::create_dir_all(dir())
+        .with_context(|| format!("failed to create {}", dir().display()))?;
+    db::save_to(db, &path)?;
+    let backups = backups(db)?;
+    for old in &backups[..backups.len().saturating_sub(keep.max(1))] {
+        std::fs::remove_file(old).with_context(|| format!("failed to remove {}", old.display()))?;
+    }
+    Ok(path)
+}
+
+/// Backs up the database before a destructive command when `auto_backup` is enabled
+pub fn auto(db: &Database, config: &Config) -> Result<()> {
+    if config.auto_backup {
+        let path = create(db, config.backup_keep)?;
+        eprintln!(
+            "{} backed up to {}",
+            "Info:".yellow().bold(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn list(db: &Database, config: &Config) -> Result<()> {
+    let backups = backups(db)?;
+    if backups.is_empty() {
+        println!("{} no backups yet", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let (stem, ext) = stem_and_ext(db);
+    let mut table = new_table(["Backup", "Taken", "Bookmarks"]);
+    for path in backups.iter().rev() {
+        let taken = taken_at(path, &stem, &ext).expect("listed backups have timestamps");
+        let taken = taken.and_local_timezone(Local).single();
+        let count = db::load_from(path, Access::Read).map(|b| b.bookmarks.len());
+        table.push_record([
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            taken.map_or("-".into(), |t| config.format_time(t.to_utc())),
+            count.map_or("?".into(), |n| n.to_string()),
+        ]);
+    }
+    print_table(table);
+    Ok(())
+}
+
+/// Replaces the database with a backup, after backing up its current state
+pub fn restore(db: &mut Database, config: &Config, from: &Path) -> Result<()> {
+    let backup = db::load_from(from, Access::Read)
+        .with_context(|| format!("failed to load backup {}", from.display()))?;
+    let current = create(db, config.backup_keep.saturating_add(1))?;
+    db.replace_with(backup);
+    db::save(db)?;
+    println!(
+        "{} restored {} bookmarks from {}, the previous ones were backed up to {}",
+        "Success:".green().bold(),
+        db.bookmarks.len(),
+        from.display(),
+        current.display()
+    );
+    Ok(())
+}
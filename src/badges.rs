@@ -0,0 +1,115 @@
+use crate::db::Bookmark;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy)]
+pub enum Badge {
+    Pinned,
+    Temporary,
+    Broken,
+    Remote,
+}
+
+impl Badge {
+    fn symbol(self, ascii: bool) -> char {
+        match (self, ascii) {
+            (Badge::Pinned, false) => '★',
+            (Badge::Pinned, true) => '*',
+            (Badge::Temporary, false) => '⌛',
+            (Badge::Temporary, true) => 't',
+            (Badge::Broken, false) => '✗',
+            (Badge::Broken, true) => 'x',
+            (Badge::Remote, false) => '⇄',
+            (Badge::Remote, true) => '~',
+        }
+    }
+}
+
+/// Computes the badge column for `list`, mount info is read once per listing
+pub struct Badger {
+    ascii: bool,
+    remote_mounts: Vec<PathBuf>,
+}
+
+impl Badger {
+    pub fn new(force_ascii: bool) -> Self {
+        Self {
+            ascii: force_ascii || !unicode_locale(),
+            remote_mounts: remote_mounts(),
+        }
+    }
+
+    /// Badges of a bookmark, `path` is where it leads with references followed
+    pub fn badges(&self, bookmark: &Bookmark, path: &str) -> String {
+        let path = Path::new(path);
+        let mut badges = vec![];
+        if bookmark.pinned {
+            badges.push(Badge::Pinned);
+        }
+        if bookmark.expires.is_some() {
+            badges.push(Badge::Temporary);
+        }
+        if !path.is_dir() {
+            badges.push(Badge::Broken);
+        }
+        if self.is_remote(path) {
+            badges.push(Badge::Remote);
+        }
+        badges.into_iter().map(|b| b.symbol(self.ascii)).collect()
+    }
+
+    fn is_remote(&self, path: &Path) -> bool {
+        if cfg!(windows) && path.to_string_lossy().starts_with(r"\\") {
+            return true;
+        }
+        self.remote_mounts
+            .iter()
+            .any(|mount| path.starts_with(mount))
+    }
+}
+
+fn unicode_locale() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|val| !val.is_empty())
+        .is_some_and(|val| {
+            let val = val.to_lowercase();
+            val.contains("utf-8") || val.contains("utf8")
+        })
+}
+
+const REMOTE_FS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "afs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.davfs2",
+];
+
+/// Mount points of network filesystems, as listed in `/proc/self/mounts`
+fn remote_mounts() -> Vec<PathBuf> {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return vec![];
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let (mount, fstype) = (fields.next()?, fields.next()?);
+            REMOTE_FS
+                .contains(&fstype)
+                .then(|| PathBuf::from(mount.replace("\\040", " ")))
+        })
+        .collect()
+}
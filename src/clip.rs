@@ -0,0 +1,172 @@
+//! Clipped directories, kept in a state file of their own rather than with the
+//! bookmarks, since they're scratch data that changes all the time.
+
+use crate::{
+    config::Config,
+    db::{self, Bookmark, Database},
+    is_clip, new_table, print_table, storage,
+};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Clips {
+    /// The default register
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clip: Option<Bookmark>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    registers: BTreeMap<String, Bookmark>,
+}
+
+/// `markd/clip.toml` in the state directory
+fn path() -> PathBuf {
+    db::state_home().join("clip.toml")
+}
+
+impl Clips {
+    /// Reads the clipped directories, leaving out the ones whose time ran out
+    pub fn load() -> Result<Self> {
+        let path = path();
+        let mut clips: Clips = match std::fs::read_to_string(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Clips::default(),
+            raw => {
+                let raw = raw.with_context(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&raw)
+                    .with_context(|| format!("failed to parse {}", path.display()))?
+            }
+        };
+        let now = db::now();
+        clips.clip = clips.clip.filter(|b| !b.is_expired(now));
+        clips.registers.retain(|_, b| !b.is_expired(now));
+        Ok(clips)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path();
+        if self.clip.is_none() && self.registers.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    Err(err).with_context(|| format!("failed to remove {}", path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let toml = toml::to_string(self).context("failed to serialize clipped directories")?;
+        storage::write_atomic(&path, toml.as_bytes())
+    }
+
+    pub fn get(&self, register: Option<&str>) -> Option<&Bookmark> {
+        match register {
+            Some(register) => self.registers.get(register),
+            None => self.clip.as_ref(),
+        }
+    }
+
+    pub fn set(&mut self, register: Option<String>, entry: Bookmark) {
+        match register {
+            Some(register) => {
+                self.registers.insert(register, entry);
+            }
+            None => self.clip = Some(entry),
+        }
+    }
+
+    pub fn take(&mut self, register: Option<&str>) -> Option<Bookmark> {
+        match register {
+            Some(register) => self.registers.remove(register),
+            None => self.clip.take(),
+        }
+    }
+
+    /// Every clipped entry, the default register first
+    fn iter(&self) -> impl Iterator<Item = (Option<&str>, &Bookmark)> {
+        let clip = self.clip.iter().map(|b| (None, b));
+        clip.chain(self.registers.iter().map(|(r, b)| (Some(r.as_str()), b)))
+    }
+
+    /// Register clipped most recently
+    fn last(&self) -> Option<Option<String>> {
+        self.iter()
+            .max_by_key(|(_, b)| b.created)
+            .map(|(register, _)| register.map(String::from))
+    }
+}
+
+fn label(register: Option<&str>) -> String {
+    match register {
+        Some(register) => format!("register {register}"),
+        None => "the clip".into(),
+    }
+}
+
+pub fn clip(register: Option<String>, entry: Bookmark) -> Result<()> {
+    let mut clips = Clips::load()?;
+    let msg = format!("{} saved to {}", entry.path, label(register.as_deref()));
+    clips.set(register, entry);
+    clips.save()?;
+    println!("{} {msg}", "Success:".green().bold());
+    Ok(())
+}
+
+pub fn list(config: &Config) -> Result<()> {
+    let clips = Clips::load()?;
+    let mut entries: Vec<_> = clips.iter().collect();
+    if entries.is_empty() {
+        println!("{} nothing clipped", "Info:".yellow().bold());
+        return Ok(());
+    }
+    entries.sort_by_key(|(_, b)| std::cmp::Reverse(b.created));
+    let mut table = new_table(["Register", "Path", "Clipped"]);
+    for (register, b) in entries {
+        let clipped = b.created.map_or("-".into(), |t| config.format_time(t));
+        table.push_record([register.unwrap_or("-").into(), b.path.clone(), clipped]);
+    }
+    print_table(table);
+    Ok(())
+}
+
+/// Clears a register and returns what was in it. Without a register, the one clipped
+/// last is taken, so clipping a few directories and popping them works like a stack.
+pub fn pop(register: Option<&str>) -> Result<Bookmark> {
+    let mut clips = Clips::load()?;
+    let register = match register {
+        Some(register) => Some(register.to_string()),
+        None => clips.last().context("nothing clipped")?,
+    };
+    let entry = clips
+        .take(register.as_deref())
+        .with_context(|| format!("{} is empty", label(register.as_deref())))?;
+    clips.save()?;
+    Ok(entry)
+}
+
+/// Moves clip entries older versions saved with the bookmarks to the state file,
+/// unless the register was clipped again since. Returns whether there were any.
+pub fn migrate(db: &mut Database) -> Result<bool> {
+    let leftovers: Vec<_> = db
+        .bookmarks
+        .keys()
+        .filter(|name| is_clip(name) && db.source_of(name) == db.source)
+        .cloned()
+        .collect();
+    if leftovers.is_empty() {
+        return Ok(false);
+    }
+    let mut clips = Clips::load()?;
+    let prefix = format!("{}-", crate::clipname());
+    for name in leftovers {
+        let entry = db.bookmarks.remove(&name).expect("name was just listed");
+        let register = name.strip_prefix(&prefix).map(String::from);
+        if clips.get(register.as_deref()).is_none() {
+            clips.set(register, entry);
+        }
+    }
+    clips.save()?;
+    Ok(true)
+}
@@ -0,0 +1,233 @@
+use crate::{
+    list::{Column, Columns, PathStyle, SortBy},
+    storage::Backend,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, Utc,
+};
+use clap::ValueEnum;
+use dirs::config_dir;
+use serde::Deserialize;
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Keys that can also be set with a `MARKD_<KEY>` environment variable, which
+/// takes precedence over the config file
+const ENV_KEYS: &[&str] = &[
+    "shorthand_jump",
+    "ascii",
+    "date_format",
+    "utc",
+    "sort",
+    "columns",
+    "path_style",
+    "max_path_width",
+    "plain",
+    "pager",
+    "color",
+    "table_style",
+    "confirm",
+    "db",
+    "storage",
+    "profile",
+    "clip_name",
+    "auto_backup",
+    "backup_keep",
+    "trash_days",
+    "history_size",
+    "sync_repo",
+];
+
+/// User settings read from `markd/config.toml` in the platform config directory.
+/// Every key is optional, missing keys fall back to their defaults.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Treat `markd <name>` as `markd get <name>` when `name` is an existing bookmark
+    pub shorthand_jump: bool,
+    /// Use ASCII symbols for status badges even on unicode capable terminals
+    pub ascii: bool,
+    /// strftime-style format used wherever timestamps are displayed
+    pub date_format: String,
+    /// Display timestamps in UTC instead of the local timezone
+    pub utc: bool,
+    /// Default order of `list`
+    pub sort: SortBy,
+    /// Columns of the `list` table, the ones with values when unset
+    pub columns: Option<Columns>,
+    /// Columns added to the `list` table by flags like `--git`, never read from the file
+    #[serde(skip)]
+    pub extra_columns: Vec<Column>,
+    /// How paths are shortened in the `list` table
+    pub path_style: PathStyle,
+    /// Paths in the `list` table longer than this are cut at the start
+    pub max_path_width: Option<usize>,
+    /// Print plain `name:path` lines from `list` instead of a table
+    pub plain: bool,
+    /// Show `list` tables taller than the terminal through `$PAGER`
+    pub pager: bool,
+    pub color: ColorChoice,
+    pub table_style: TableStyle,
+    /// Ask before overwriting bookmarks or editing shell config files
+    pub confirm: bool,
+    /// Location of the bookmarks database
+    pub db: Option<PathBuf>,
+    /// Backend of the bookmarks database, picked by its file extension when unset
+    pub storage: Option<Backend>,
+    /// Bookmark profile to use, each one is kept in its own file
+    pub profile: Option<String>,
+    /// Name older versions saved the `clip` entry under in the bookmarks
+    pub clip_name: String,
+    /// Back up the bookmarks before `remove` and `purge`
+    pub auto_backup: bool,
+    /// Number of backups to keep, older ones are deleted
+    pub backup_keep: usize,
+    /// Days removed and overwritten bookmarks stay in the trash
+    pub trash_days: u32,
+    /// Number of `get` resolutions `history` remembers, 0 turns it off
+    pub history_size: usize,
+    /// Git working copy `sync` keeps the bookmarks in
+    pub sync_repo: Option<PathBuf>,
+}
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color unless disabled with NO_COLOR
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Deserialize, ValueEnum, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TableStyle {
+    #[default]
+    Rounded,
+    /// Only ASCII characters, for dumb terminals
+    Ascii,
+    /// Ready to paste into markdown documents
+    Markdown,
+    /// Only the line under the header
+    Minimal,
+    /// No borders at all
+    None,
+}
+
+impl ColorChoice {
+    pub fn apply(self) {
+        match self {
+            ColorChoice::Auto => {}
+            ColorChoice::Always => colored::control::set_override(true),
+            ColorChoice::Never => colored::control::set_override(false),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            shorthand_jump: false,
+            ascii: false,
+            date_format: "%Y-%m-%d %H:%M".into(),
+            utc: false,
+            sort: SortBy::Name,
+            columns: None,
+            extra_columns: vec![],
+            path_style: PathStyle::Full,
+            max_path_width: None,
+            plain: false,
+            pager: true,
+            color: ColorChoice::Auto,
+            table_style: TableStyle::Rounded,
+            confirm: true,
+            db: None,
+            storage: None,
+            profile: None,
+            clip_name: "markd-temp".into(),
+            auto_backup: false,
+            backup_keep: 10,
+            trash_days: 30,
+            history_size: 1000,
+            sync_repo: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let mut table = match config_path() {
+            Some(path) => read_table(&path)?,
+            None => toml::Table::new(),
+        };
+        let mut from_env = vec![];
+        for key in ENV_KEYS {
+            if let Ok(raw) = std::env::var(format!("MARKD_{}", key.to_uppercase())) {
+                table.insert(key.to_string(), env_value(raw));
+                from_env.push(*key);
+            }
+        }
+        // `db` wins over `profile`, unless only the profile comes from the environment
+        if from_env.contains(&"profile") && !from_env.contains(&"db") {
+            table.remove("db");
+        }
+        let config = Self::deserialize(table)
+            .context("invalid config, check config.toml and MARKD_* environment variables")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if StrftimeItems::new(&self.date_format).any(|item| item == Item::Error) {
+            bail!("invalid date_format in config: {:?}", self.date_format);
+        }
+        if let Some(Err(err)) = self.profile.as_deref().map(crate::profile::parse_name) {
+            bail!("invalid profile in config: {err}");
+        }
+        if self.clip_name.trim().is_empty() {
+            bail!("clip_name in config can't be empty");
+        }
+        Ok(())
+    }
+
+    pub fn format_time(&self, time: DateTime<Utc>) -> String {
+        if self.utc {
+            time.format(&self.date_format).to_string()
+        } else {
+            time.with_timezone(&Local)
+                .format(&self.date_format)
+                .to_string()
+        }
+    }
+}
+
+/// A missing config file is treated as an empty one
+fn read_table(path: &Path) -> Result<toml::Table> {
+    match std::fs::read_to_string(path) {
+        Ok(raw) => {
+            toml::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Environment values are read as TOML (`true`, `3`) and fall back to plain strings
+fn env_value(raw: String) -> toml::Value {
+    match toml::from_str::<toml::Table>(&format!("value = {raw}")) {
+        Ok(mut table) => table.remove("value").expect("parsed key"),
+        Err(_) => toml::Value::String(raw),
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let mut path = config_dir()?;
+    path.push("markd");
+    path.push("config.toml");
+    Some(path)
+}
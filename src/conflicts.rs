@@ -0,0 +1,100 @@
+//! Conflicted copies left next to the bookmarks file by Dropbox, Syncthing and
+//! similar tools, e.g. `bookmarks (conflicted copy 2024-05-01).toml` or
+//! `bookmarks.sync-conflict-20240501-101010-ABCDEFG.toml`.
+
+use crate::{
+    db::{self, Bookmark, Database},
+    storage::Backend,
+    trash::Trash,
+};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// Merges any conflicted copies of the database's file into it and removes them
+pub fn resolve(db: &mut Database) -> Result<()> {
+    let Some(file) = db.file() else {
+        return Ok(());
+    };
+    let copies = find(file)?;
+    if copies.is_empty() {
+        return Ok(());
+    }
+    // Every removal since an entry was last modified, whichever side made it
+    let trash = Trash::open(db, u32::MAX)?;
+    let removals = trash.removals();
+    let removed = |name: &str, entry: &Bookmark| {
+        removals
+            .get(name)
+            .is_some_and(|&at| Some(at) > modified(entry))
+    };
+    let mut merged = db.own();
+    for copy in &copies {
+        let theirs = Backend::detect(copy).open(copy)?.load()?;
+        // Entries only one side has were either added there or removed on the other
+        merged.retain(|name, entry| theirs.contains_key(name) || !removed(name, entry));
+        for (name, entry) in theirs {
+            match merged.get_mut(&name) {
+                None if removed(&name, &entry) => {}
+                None => {
+                    merged.insert(name, entry);
+                }
+                Some(ours) if ours.path == entry.path => {
+                    ours.absorb(&entry);
+                }
+                Some(ours) if modified(&entry) > modified(ours) => *ours = entry,
+                Some(_) => {}
+            }
+        }
+    }
+    db.replace_own(merged);
+    db::save(db)?;
+    for copy in &copies {
+        std::fs::remove_file(copy)
+            .with_context(|| format!("failed to remove {}", copy.display()))?;
+    }
+    eprintln!(
+        "{} merged {} conflicted copies of the bookmarks file",
+        "Info:".yellow().bold(),
+        copies.len()
+    );
+    Ok(())
+}
+
+/// Last time the entry was known to change, its creation for entries saved before
+/// modifications were tracked
+fn modified(entry: &Bookmark) -> Option<chrono::DateTime<chrono::Utc>> {
+    entry.modified.or(entry.created)
+}
+
+fn find(file: &Path) -> Result<Vec<PathBuf>> {
+    let (Some(dir), Some(stem)) = (file.parent(), file.file_stem()) else {
+        return Ok(vec![]);
+    };
+    let stem = stem.to_string_lossy();
+    let ext = file
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(vec![]),
+    };
+    let mut copies = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(middle) = name
+            .strip_prefix(stem.as_ref())
+            .and_then(|rest| rest.strip_suffix(&ext))
+        else {
+            continue;
+        };
+        let conflicted = middle.to_lowercase().contains("conflicted copy")
+            || middle.starts_with(".sync-conflict-");
+        if conflicted && entry.path().is_file() {
+            copies.push(entry.path());
+        }
+    }
+    copies.sort();
+    Ok(copies)
+}
@@ -0,0 +1,703 @@
+use crate::{
+    conflicts, journal,
+    storage::{self, Backend, Storage},
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, SubsecRound, Utc};
+use colored::Colorize;
+use dirs::{data_dir, home_dir};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+static DB_PATH: OnceCell<PathBuf> = OnceCell::new();
+static BACKEND: OnceCell<Backend> = OnceCell::new();
+static HOSTNAME: Lazy<Option<String>> = Lazy::new(|| hostname::get().ok()?.into_string().ok());
+
+pub type Bookmarks = BTreeMap<String, Bookmark>;
+
+#[derive(Default)]
+pub struct Database {
+    /// Own entries along with the ones merged in from other files
+    pub bookmarks: Bookmarks,
+    /// Entries merged in from other files, never saved unless they get modified
+    pub layered: BTreeMap<String, Layered>,
+    /// Where this database's own entries come from
+    pub source: Source,
+    /// Where the database was loaded from and gets saved to
+    storage: Option<Box<dyn Storage>>,
+    /// File and backend the database was loaded from, to reopen it for writing
+    origin: Option<(PathBuf, Backend)>,
+    /// Number of journal entries replayed on load
+    pub journaled: usize,
+    access: Access,
+    /// Paths under this directory are stored relative to it (project files)
+    root: Option<PathBuf>,
+    /// Entries scoped to other hosts, kept untouched until the next save
+    elsewhere: Bookmarks,
+    /// Entries put away with `archive`, out of the way of every other command
+    pub archived: Bookmarks,
+    /// Entries whose time to live ran out, kept until `expire` removes them
+    pub expired: Bookmarks,
+    /// Own entries as they were loaded, to tell which ones a save modifies
+    loaded: Bookmarks,
+    /// Held until the database is dropped, so concurrent runs can't interleave
+    /// their reads and writes and lose each other's changes
+    _lock: Option<File>,
+}
+
+/// How a database is opened. Writable ones are locked for the whole run, read-only
+/// ones are loaded without locking, creating or touching anything and can't be saved.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Access {
+    Read,
+    #[default]
+    Write,
+}
+
+/// Files bookmarks can come from when they are layered on top of each other
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Source {
+    #[default]
+    User,
+    Project,
+    System,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::User => "user",
+            Source::Project => "project",
+            Source::System => "system",
+        }
+    }
+}
+
+pub struct Layered {
+    pub source: Source,
+    /// The entry as found in its file, to tell whether it was modified
+    original: Bookmark,
+    /// This database's own entry hidden by the layered one
+    shadowed: Option<Bookmark>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Bookmark {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Unknown for entries created before timestamps were tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// Last time the bookmark was resolved with `get`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub used: Option<DateTime<Utc>>,
+    /// Number of times the bookmark was resolved with `get`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hits: u64,
+    /// Hostnames the bookmark is valid on, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub archived: bool,
+    /// Listed first, preferred when a lookup is ambiguous and never purged
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+    /// Set aside once this time has passed and removed by `expire`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    /// Other names of the bookmark, sharing its path and metadata
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Last time the entry itself changed, set on save. Usage doesn't count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+impl Database {
+    pub fn new(bookmarks: Bookmarks) -> Self {
+        Self {
+            bookmarks,
+            ..Default::default()
+        }
+    }
+
+    /// Sets aside the entries that don't apply to this host, the archived and the
+    /// expired ones
+    fn set_aside(&mut self) {
+        let (here, elsewhere): (Bookmarks, _) = std::mem::take(&mut self.bookmarks)
+            .into_iter()
+            .partition(|(_, b)| b.on_this_host());
+        let (archived, active): (Bookmarks, _) = here.into_iter().partition(|(_, b)| b.archived);
+        let now = now();
+        let (expired, active) = active.into_iter().partition(|(_, b)| b.is_expired(now));
+        self.bookmarks = active;
+        self.archived = archived;
+        self.expired = expired;
+        self.elsewhere = elsewhere;
+    }
+
+    /// Merges in bookmarks from another file. Layers `above` this database hide its
+    /// own entries with the same name, others only add the names it doesn't have.
+    pub fn merge(&mut self, source: Source, bookmarks: Bookmarks, above: bool) {
+        for (name, bookmark) in bookmarks {
+            if !above && self.bookmarks.contains_key(&name) {
+                continue;
+            }
+            let shadowed = self.bookmarks.insert(name.clone(), bookmark.clone());
+            let shadowed = match self.layered.remove(&name) {
+                Some(layered) => layered.shadowed,
+                None => shadowed,
+            };
+            let layered = Layered {
+                source,
+                original: bookmark,
+                shadowed,
+            };
+            self.layered.insert(name, layered);
+        }
+    }
+
+    /// Takes over another database's own entries, as when restoring a backup
+    pub fn replace_with(&mut self, other: Database) {
+        self.bookmarks = other.bookmarks;
+        self.elsewhere = other.elsewhere;
+        self.archived = other.archived;
+        self.expired = other.expired;
+        self.layered.clear();
+    }
+
+    /// Takes over entries in the layout of this database's file, as after a sync
+    pub fn replace_own(&mut self, bookmarks: Bookmarks) {
+        self.bookmarks = bookmarks;
+        self.layered.clear();
+        self.set_aside();
+    }
+
+    /// Name of the bookmark `name` refers to, either itself or the bookmark it's an alias of
+    pub fn canonical<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        match self.bookmarks.contains_key(name) {
+            true => Some(name),
+            false => self.alias_of(name),
+        }
+    }
+
+    /// Bookmark that has `alias` among its aliases
+    pub fn alias_of(&self, alias: &str) -> Option<&str> {
+        self.bookmarks
+            .iter()
+            .find(|(_, b)| b.aliases.iter().any(|a| a == alias))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// File the database was loaded from
+    pub fn file(&self) -> Option<&Path> {
+        self.origin.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    pub fn backend(&self) -> Option<Backend> {
+        self.origin.as_ref().map(|(_, backend)| *backend)
+    }
+
+    pub fn source_of(&self, name: &str) -> Source {
+        self.layered.get(name).map_or(self.source, |l| l.source)
+    }
+
+    /// Entries that belong in this database's file. Merged entries are left out,
+    /// unless they were modified, in which case they become own entries.
+    pub fn own(&self) -> Bookmarks {
+        let mut own = Bookmarks::new();
+        for (name, bookmark) in &self.bookmarks {
+            let entry = match self.layered.get(name) {
+                Some(layered) if layered.original.same_entry(bookmark) => layered.shadowed.clone(),
+                _ => Some(bookmark.clone()),
+            };
+            if let Some(mut entry) = entry {
+                if let Some(relative) = self
+                    .root
+                    .as_ref()
+                    .and_then(|root| Path::new(&entry.path).strip_prefix(root).ok())
+                {
+                    entry.path = relative.to_string_lossy().to_string();
+                }
+                own.insert(name.clone(), entry);
+            }
+        }
+        let aside = self.archived.iter().chain(&self.expired);
+        for (name, entry) in aside.chain(&self.elsewhere) {
+            own.entry(name.clone()).or_insert_with(|| entry.clone());
+        }
+        own
+    }
+
+    /// Own entries with `modified` set on the ones that changed since loading,
+    /// unless the change brought its own timestamp along
+    fn stamped(&self) -> Bookmarks {
+        let now = now();
+        let mut own = self.own();
+        for (name, entry) in own.iter_mut() {
+            let stamp = match self.loaded.get(name) {
+                Some(before) => entry.edited(before) && entry.modified == before.modified,
+                None => entry.modified.is_none(),
+            };
+            if stamp {
+                entry.modified = Some(now);
+            }
+        }
+        own
+    }
+}
+
+impl Bookmark {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            tags: vec![],
+            note: None,
+            created: Some(now()),
+            used: None,
+            hits: 0,
+            hosts: vec![],
+            archived: false,
+            pinned: false,
+            expires: None,
+            aliases: vec![],
+            modified: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    pub fn record_use(&mut self, at: DateTime<Utc>) {
+        self.used = Some(at);
+        self.hits += 1;
+    }
+
+    /// zoxide style ranking, visit count weighted by how recently it was last used
+    pub fn frecency(&self, now: DateTime<Utc>) -> f64 {
+        self.used.map_or(0.0, |used| frecency(self.hits, used, now))
+    }
+
+    /// Whether both describe the same bookmark, ignoring usage statistics
+    pub fn same_entry(&self, other: &Bookmark) -> bool {
+        self.path == other.path
+            && self.tags == other.tags
+            && self.note == other.note
+            && self.aliases == other.aliases
+            && self.hosts == other.hosts
+            && self.pinned == other.pinned
+            && self.expires == other.expires
+    }
+
+    /// Whether anything but usage statistics differs from `before`
+    fn edited(&self, before: &Bookmark) -> bool {
+        let strip = |b: &Bookmark| Bookmark {
+            used: None,
+            hits: 0,
+            modified: None,
+            ..b.clone()
+        };
+        strip(self) != strip(before)
+    }
+
+    /// Folds in the metadata of `other`, an entry for the same directory from another
+    /// database. Returns whether anything changed.
+    pub fn absorb(&mut self, other: &Bookmark) -> bool {
+        let before = self.clone();
+        let tags = other.tags.clone();
+        self.add_tags(&tags);
+        if self.note.is_none() {
+            self.note = other.note.clone();
+        }
+        self.created = match (self.created, other.created) {
+            (Some(ours), Some(theirs)) => Some(ours.min(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+        self.used = self.used.max(other.used);
+        self.hits = self.hits.max(other.hits);
+        self.modified = self.modified.max(other.modified);
+        self.pinned |= other.pinned;
+        // Permanent beats temporary, otherwise the later expiry wins
+        self.expires = match (self.expires, other.expires) {
+            (Some(ours), Some(theirs)) => Some(ours.max(theirs)),
+            _ => None,
+        };
+        for host in &other.hosts {
+            if !self.hosts.contains(host) {
+                self.hosts.push(host.clone());
+            }
+        }
+        for alias in &other.aliases {
+            if !self.aliases.contains(alias) {
+                self.aliases.push(alias.clone());
+            }
+        }
+        before != *self
+    }
+
+    pub fn on_this_host(&self) -> bool {
+        match HOSTNAME.as_deref() {
+            Some(host) if !self.hosts.is_empty() => {
+                self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+            }
+            _ => true,
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Whether the bookmark has all of `tags`, or at least one of them when `any` is set
+    pub fn has_tags(&self, tags: &[String], any: bool) -> bool {
+        match any {
+            true => tags.is_empty() || tags.iter().any(|tag| self.has_tag(tag)),
+            false => tags.iter().all(|tag| self.has_tag(tag)),
+        }
+    }
+
+    /// Adds the given tags, returns the ones that weren't already present
+    pub fn add_tags<'a>(&mut self, tags: &'a [String]) -> Vec<&'a str> {
+        let mut added = vec![];
+        for tag in tags {
+            if !self.has_tag(tag) {
+                self.tags.push(tag.clone());
+                added.push(tag.as_str());
+            }
+        }
+        self.tags.sort();
+        added
+    }
+
+    /// Removes the given tags, returns the ones that were present
+    pub fn remove_tags<'a>(&mut self, tags: &'a [String]) -> Vec<&'a str> {
+        let removed = tags.iter().filter(|t| self.has_tag(t)).map(String::as_str);
+        let removed: Vec<_> = removed.collect();
+        self.tags.retain(|t| !tags.contains(t));
+        removed
+    }
+}
+
+/// Current time, truncated to whole seconds to keep the database readable
+pub fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(0)
+}
+
+/// Tags are case-insensitive and can't be empty
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    match tag.is_empty() {
+        true => Err("tags can't be empty".into()),
+        false => Ok(tag),
+    }
+}
+
+/// Parses a span of time like `12h`, `30d`, `6w`, `3m` (30 day months) or `1y`
+pub fn parse_age(age: &str) -> Result<chrono::Duration, String> {
+    let age = age.trim();
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (count, unit) = age.split_at(split);
+    let count: i64 = count
+        .parse()
+        .map_err(|_| format!("expected a number followed by a unit, got {age:?}"))?;
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        "m" => 24 * 30,
+        "y" => 24 * 365,
+        _ => return Err(format!("unknown unit {unit:?}, use h, d, w, m or y")),
+    };
+    count
+        .checked_mul(hours)
+        .and_then(chrono::Duration::try_hours)
+        .ok_or_else(|| format!("{age} is too long"))
+}
+
+/// Overrides the database location, has no effect after the first load or save
+pub fn set_path(path: PathBuf) {
+    let _ = DB_PATH.set(path);
+}
+
+pub fn path() -> &'static Path {
+    DB_PATH.get_or_init(default_path)
+}
+
+/// Overrides the backend of the database, which is otherwise picked by file extension
+pub fn set_backend(backend: Backend) {
+    let _ = BACKEND.set(backend);
+}
+
+pub fn load(access: Access) -> Result<Database> {
+    let backend = BACKEND.get().copied();
+    open(
+        path(),
+        backend.unwrap_or_else(|| Backend::detect(path())),
+        access,
+    )
+}
+
+pub fn load_from(path: &Path, access: Access) -> Result<Database> {
+    open(path, Backend::detect(path), access)
+}
+
+fn open(path: &Path, backend: Backend, access: Access) -> Result<Database> {
+    // Read-only commands read an old database where it is, the next write moves it
+    if access == Access::Read && path == default_path() {
+        if let Some(legacy) = legacy(path) {
+            return open(&legacy, backend, access);
+        }
+    }
+    let lock = match access {
+        Access::Read => None,
+        Access::Write => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            let lock = lock(path)?;
+            if path == default_path() {
+                move_legacy(path)?;
+            }
+            lock
+        }
+    };
+    let storage = backend.open(path)?;
+    let mut db = Database::new(storage.load()?);
+    if !matches!(backend, Backend::Memory) {
+        db.journaled = journal::replay(&sibling(path, "journal"), &mut db.bookmarks)?;
+    }
+    db.set_aside();
+    db.loaded = db.own();
+    db.storage = Some(storage);
+    db.origin = Some((path.to_path_buf(), backend));
+    db.access = access;
+    db._lock = lock;
+    if access == Access::Write && !matches!(backend, Backend::Memory) {
+        conflicts::resolve(&mut db)?;
+    }
+    Ok(db)
+}
+
+/// Bumps the usage statistics of a bookmark by appending to the journal instead of
+/// rewriting the database, and does nothing when the file can't be written to.
+pub fn record_use(db: &mut Database, name: &str) -> Result<()> {
+    let op = journal::Op::Use {
+        name: name.to_string(),
+        at: now(),
+    };
+    op.apply(&mut db.bookmarks);
+    // Merged entries belong to other files
+    if db.layered.contains_key(name) {
+        return Ok(());
+    }
+    let Some((path, backend)) = &db.origin else {
+        return Ok(());
+    };
+    if matches!(backend, Backend::Memory) {
+        return Ok(());
+    }
+    // Writable databases already hold the lock, which can't be taken twice
+    let lock = match db.access {
+        Access::Read => lock(path),
+        Access::Write => Ok(None),
+    };
+    let result = lock.and_then(|_lock| journal::append(&sibling(path, "journal"), &op));
+    match result {
+        Err(err) if is_read_only(&err) => Ok(()),
+        result => result,
+    }
+}
+
+pub fn is_read_only(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|err| {
+            matches!(
+                err.kind(),
+                ErrorKind::ReadOnlyFilesystem | ErrorKind::PermissionDenied
+            )
+        })
+}
+
+/// `<path>.<ext>`, for the files kept next to the database
+pub fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(".");
+    sibling.push(ext);
+    PathBuf::from(sibling)
+}
+
+/// Takes an exclusive advisory lock on `<path>.lock`, waiting for other runs to finish
+fn lock(path: &Path) -> Result<Option<File>> {
+    let lock_path = sibling(path, "lock");
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("failed to open {}", lock_path.display()))?;
+    file.lock()
+        .with_context(|| format!("failed to lock {}", lock_path.display()))?;
+    Ok(Some(file))
+}
+
+/// Loads a project file, whose relative paths point inside the project
+pub fn load_project(path: &Path, access: Access) -> Result<Database> {
+    let mut db = load_from(path, access)?;
+    let root = path
+        .parent()
+        .context("project file has no parent directory")?;
+    for bookmark in db.bookmarks.values_mut() {
+        bookmark.path = root.join(&bookmark.path).to_string_lossy().to_string();
+    }
+    db.root = Some(root.to_path_buf());
+    db.source = Source::Project;
+    Ok(db)
+}
+
+/// Admin provided bookmarks shared by every user of the machine
+pub fn system_path() -> Option<PathBuf> {
+    match cfg!(windows) {
+        true => {
+            std::env::var_os("ProgramData").map(|dir| Path::new(&dir).join(r"markd\bookmarks.toml"))
+        }
+        false => Some(PathBuf::from("/etc/markd/bookmarks.toml")),
+    }
+}
+
+/// Merges the system bookmarks underneath the loaded ones, if there are any
+pub fn merge_system(db: &mut Database) -> Result<()> {
+    let Some(path) = system_path().filter(|path| path.is_file()) else {
+        return Ok(());
+    };
+    let mut system = Database::new(storage::read_toml(&path)?);
+    system.set_aside();
+    db.merge(Source::System, system.bookmarks, false);
+    Ok(())
+}
+
+pub fn save(db: &Database) -> Result<()> {
+    if db.access == Access::Read {
+        bail!("bookmarks were loaded read-only and can't be saved");
+    }
+    match &db.storage {
+        Some(storage) => storage.save(&db.stamped())?,
+        None => return save_to(db, path()),
+    }
+    // Replayed entries are part of what was just saved
+    match &db.origin {
+        Some((path, _)) => journal::clear(&sibling(path, "journal")),
+        None => Ok(()),
+    }
+}
+
+/// Saves the database to another file, in the format matching its extension
+pub fn save_to(db: &Database, path: &Path) -> Result<()> {
+    Backend::detect(path).open(path)?.save(&db.own())
+}
+
+pub fn default_path() -> PathBuf {
+    data_home().join("bookmarks.toml")
+}
+
+/// `markd` directory inside the platform data directory
+pub fn data_home() -> PathBuf {
+    let mut path = data_dir().expect("failed to get data directory");
+    path.push("markd");
+    path
+}
+
+/// `markd` directory inside the platform state directory, or the data directory on
+/// platforms without one
+pub fn state_home() -> PathBuf {
+    dirs::state_dir()
+        .map(|dir| dir.join("markd"))
+        .unwrap_or_else(data_home)
+}
+
+/// Visit count weighted by how long ago the last visit was
+pub fn frecency(hits: u64, used: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    let weight = match (now - used).num_hours() {
+        ..=0 => 4.0,
+        1..=23 => 2.0,
+        24..=167 => 0.5,
+        _ => 0.25,
+    };
+    hits as f64 * weight
+}
+
+/// Older versions kept the database in `$HOME/bookmarks.toml`, which is still in use
+/// until a database exists at `path`
+fn legacy(path: &Path) -> Option<PathBuf> {
+    let legacy = home_dir()?.join("bookmarks.toml");
+    (!path.exists() && legacy.is_file()).then_some(legacy)
+}
+
+/// Moves the legacy database to the data directory, called with the lock held
+fn move_legacy(path: &Path) -> Result<()> {
+    let Some(legacy) = legacy(path) else {
+        return Ok(());
+    };
+    // Renaming fails across filesystems, fall back to copying
+    if std::fs::rename(&legacy, path).is_err() {
+        std::fs::copy(&legacy, path).with_context(|| {
+            format!("failed to copy {} to {}", legacy.display(), path.display())
+        })?;
+        std::fs::remove_file(&legacy)
+            .with_context(|| format!("failed to remove {}", legacy.display()))?;
+    }
+    eprintln!(
+        "{} moved {} to {}",
+        "Info:".yellow().bold(),
+        legacy.display(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Databases for unit tests
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    /// Fresh bookmarks from `(name, path)` pairs
+    pub fn bookmarks(entries: &[(&str, &str)]) -> Bookmarks {
+        entries
+            .iter()
+            .map(|(name, path)| (name.to_string(), Bookmark::new(path.to_string())))
+            .collect()
+    }
+
+    /// `api` aliased as `backend` and `web`, along with the archived `old`. Saving
+    /// keeps it in memory.
+    pub fn database() -> Database {
+        let mut db = Database::new(bookmarks(&[("api", "/src/api"), ("web", "/src/web")]));
+        db.storage = Some(Box::new(storage::Memory::default()));
+        db.bookmarks
+            .get_mut("api")
+            .expect("just added")
+            .aliases
+            .push("backend".into());
+        db.archived = bookmarks(&[("old", "/src/old")]);
+        db
+    }
+}
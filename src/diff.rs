@@ -0,0 +1,74 @@
+//! Comparing two bookmark databases by name and path.
+
+use crate::{db::Bookmarks, is_clip, new_table, print_table};
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Default)]
+pub struct Diff<'a> {
+    only_in_a: BTreeMap<&'a str, &'a str>,
+    only_in_b: BTreeMap<&'a str, &'a str>,
+    /// Names present in both that point to different directories, as (a, b)
+    changed: BTreeMap<&'a str, (&'a str, &'a str)>,
+}
+
+impl<'a> Diff<'a> {
+    pub fn new(a: &'a Bookmarks, b: &'a Bookmarks) -> Self {
+        let mut diff = Diff::default();
+        let entries = |bookmarks: &'a Bookmarks| {
+            bookmarks
+                .iter()
+                .filter(|(name, _)| !is_clip(name))
+                .map(|(name, b)| (name.as_str(), b.path.as_str()))
+        };
+        for (name, path) in entries(a) {
+            match b.get(name) {
+                None => {
+                    diff.only_in_a.insert(name, path);
+                }
+                Some(other) if other.path != path => {
+                    diff.changed.insert(name, (path, &other.path));
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, path) in entries(b).filter(|(name, _)| !a.contains_key(*name)) {
+            diff.only_in_b.insert(name, path);
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn print(&self, a_label: &str, b_label: &str, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        if self.is_empty() {
+            println!("{} no differences found", "Info:".yellow().bold());
+            return Ok(());
+        }
+        let mut table = new_table(["Name", a_label, b_label]);
+        let missing = || "-".dimmed().to_string();
+        for (name, path) in &self.only_in_a {
+            table.push_record([name.to_string(), path.red().to_string(), missing()]);
+        }
+        for (name, path) in &self.only_in_b {
+            table.push_record([name.to_string(), missing(), path.green().to_string()]);
+        }
+        for (name, (a, b)) in &self.changed {
+            table.push_record([
+                name.to_string(),
+                a.yellow().to_string(),
+                b.yellow().to_string(),
+            ]);
+        }
+        print_table(table);
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! Health checks for the bookmarks database, with fixes for the problems that have
+//! an obvious solution.
+
+use crate::{
+    db::{self, Database},
+    is_clip, new_table, print_table, resolve,
+};
+use anyhow::Result;
+use colored::Colorize;
+use std::{collections::BTreeMap, fs::OpenOptions, path::Path};
+
+enum Fix {
+    Remove,
+    SetPath(String),
+}
+
+struct Issue {
+    bookmark: String,
+    problem: String,
+    fix: Option<Fix>,
+    /// What to do about it when it can't be fixed automatically
+    hint: &'static str,
+}
+
+pub fn doctor(db: &mut Database, fix: bool) -> Result<()> {
+    let mut issues = vec![];
+    let file = db.file().unwrap_or(db::path()).to_path_buf();
+    if let Some(problem) = file_problem(&file) {
+        issues.push(Issue {
+            bookmark: "-".into(),
+            problem,
+            fix: None,
+            hint: "check the file's permissions",
+        });
+    }
+
+    let mut by_path: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for (name, b) in &db.bookmarks {
+        let target = match resolve::expand(&db.bookmarks, &b.path) {
+            Ok(target) => target,
+            Err(err) => {
+                issues.push(Issue {
+                    bookmark: name.clone(),
+                    problem: format!("{err:#}"),
+                    fix: None,
+                    hint: "point it at an existing bookmark",
+                });
+                continue;
+            }
+        };
+        let path = Path::new(&target);
+        if is_clip(name) {
+            issues.push(Issue {
+                bookmark: name.clone(),
+                problem: "leftover clip entry".into(),
+                fix: Some(Fix::Remove),
+                hint: "",
+            });
+            continue;
+        }
+        by_path.entry(target.clone()).or_default().push(name);
+        if !path.exists() {
+            issues.push(Issue {
+                bookmark: name.clone(),
+                problem: format!("{target} no longer exists"),
+                fix: None,
+                hint: "run `markd purge`",
+            });
+        } else if !path.is_dir() {
+            let parent = path.parent().map(|p| p.to_string_lossy().to_string());
+            issues.push(Issue {
+                bookmark: name.clone(),
+                problem: format!("{target} is a file, not a directory"),
+                fix: parent
+                    .filter(|_| !b.path.starts_with('@'))
+                    .map(Fix::SetPath),
+                hint: "bookmark its directory instead",
+            });
+        } else if let Ok(canonical) = path.canonicalize() {
+            let canonical = canonical.to_string_lossy().to_string();
+            if canonical != target && !b.path.starts_with('@') {
+                issues.push(Issue {
+                    bookmark: name.clone(),
+                    problem: format!("{target} is not canonical, it resolves to {canonical}"),
+                    fix: Some(Fix::SetPath(canonical)),
+                    hint: "",
+                });
+            }
+        }
+    }
+    for names in by_path.values().filter(|names| names.len() > 1) {
+        let names: Vec<_> = names.iter().map(|n| n.as_str()).collect();
+        issues.push(Issue {
+            bookmark: names.join(", "),
+            problem: "bookmarks point to the same directory".into(),
+            fix: None,
+            hint: "keep one and use `markd alias add` for the other names",
+        });
+    }
+
+    if issues.is_empty() {
+        println!("{} no problems found", "Success:".green().bold());
+        return Ok(());
+    }
+    let mut table = new_table(["Bookmark", "Problem", "Fix"]);
+    for issue in &issues {
+        let fix = match (&issue.fix, fix) {
+            (Some(_), true) => "fixed".green().to_string(),
+            (Some(_), false) => "--fix".yellow().to_string(),
+            (None, _) => issue.hint.to_string(),
+        };
+        table.push_record([issue.bookmark.clone(), issue.problem.clone(), fix]);
+    }
+    let fixable: Vec<_> = issues
+        .into_iter()
+        .filter_map(|issue| Some((issue.bookmark, issue.fix?)))
+        .collect();
+    if fix && !fixable.is_empty() {
+        let mut count = 0;
+        for (name, fix) in fixable {
+            // Merged entries belong to other files
+            if db.source_of(&name) != db.source {
+                continue;
+            }
+            count += 1;
+            match fix {
+                Fix::Remove => {
+                    db.bookmarks.remove(&name);
+                }
+                Fix::SetPath(path) => {
+                    if let Some(entry) = db.bookmarks.get_mut(&name) {
+                        entry.path = path;
+                    }
+                }
+            }
+        }
+        db::save(db)?;
+        print_table(table);
+        println!("{} fixed {count} problems", "Success:".green().bold());
+        return Ok(());
+    }
+    print_table(table);
+    if !fixable.is_empty() {
+        println!(
+            "{} {} of these can be fixed with `markd doctor --fix`",
+            "Info:".yellow().bold(),
+            fixable.len()
+        );
+    }
+    Ok(())
+}
+
+/// Checks the bookmarks file can be read and written
+fn file_problem(file: &Path) -> Option<String> {
+    if !file.exists() {
+        return None;
+    }
+    if let Err(err) = std::fs::File::open(file) {
+        return Some(format!("can't read {}: {err}", file.display()));
+    }
+    match OpenOptions::new().append(true).open(file) {
+        Err(err) => Some(format!("can't write to {}: {err}", file.display())),
+        Ok(_) => None,
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Time spent measuring one directory before giving up, so huge trees don't stall `list`
+const TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// Total size of the files under `dir` in bytes, without following symlinks. The flag is
+/// false when the time limit was hit and the size is only a lower bound.
+pub fn size(dir: &Path) -> Option<(u64, bool)> {
+    if !dir.is_dir() {
+        return None;
+    }
+    let deadline = Instant::now() + TIME_LIMIT;
+    let mut total = 0;
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if Instant::now() > deadline {
+            return Some((total, false));
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                stack.push(entry.path());
+            } else {
+                total += meta.len();
+            }
+        }
+    }
+    Some((total, true))
+}
+
+/// Size in binary units, like `1.5 GiB`
+pub fn human(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
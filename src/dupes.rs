@@ -0,0 +1,121 @@
+//! Finding bookmarks that point to the same directory, or to directories inside
+//! other bookmarked ones.
+
+use crate::{
+    backup,
+    config::Config,
+    db::{self, Database},
+    new_table, print_table, resolve,
+};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use dialoguer::Select;
+use std::{collections::BTreeMap, path::PathBuf};
+
+pub fn dupes(db: &mut Database, config: &Config, interactive: bool) -> Result<()> {
+    let mut by_dir: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (name, b) in &db.bookmarks {
+        let target = PathBuf::from(resolve::target(&db.bookmarks, b).as_ref());
+        let dir = target.canonicalize().unwrap_or(target);
+        by_dir.entry(dir).or_default().push(name.clone());
+    }
+    let same: Vec<_> = by_dir
+        .iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(dir, names)| (dir.clone(), names.clone()))
+        .collect();
+    let mut nested = vec![];
+    for (dir, names) in &by_dir {
+        let closest = dir
+            .ancestors()
+            .skip(1)
+            .find_map(|a| by_dir.get_key_value(a));
+        if let Some((parent, outer)) = closest {
+            nested.push([
+                names.join(", "),
+                dir.display().to_string(),
+                format!("{} ({})", outer.join(", "), parent.display()),
+            ]);
+        }
+    }
+
+    if same.is_empty() && nested.is_empty() {
+        println!("{} no duplicate bookmarks found", "Success:".green().bold());
+        return Ok(());
+    }
+    if !same.is_empty() {
+        println!("{}", "Same directory:".yellow().bold());
+        let mut table = new_table(["Directory", "Bookmarks"]);
+        for (dir, names) in &same {
+            table.push_record([dir.display().to_string(), names.join(", ")]);
+        }
+        print_table(table);
+    }
+    if !nested.is_empty() {
+        println!("{}", "Inside another bookmark:".yellow().bold());
+        let mut table = new_table(["Bookmarks", "Directory", "Inside"]);
+        for row in nested {
+            table.push_record(row);
+        }
+        print_table(table);
+    }
+    if interactive && !same.is_empty() {
+        cleanup(db, config, same)?;
+    }
+    Ok(())
+}
+
+/// Asks which bookmark of each group to keep, the others become its aliases
+fn cleanup(db: &mut Database, config: &Config, groups: Vec<(PathBuf, Vec<String>)>) -> Result<()> {
+    let mut folded = vec![];
+    for (dir, names) in groups {
+        // Merged entries belong to other files
+        let names: Vec<_> = names
+            .into_iter()
+            .filter(|name| db.source_of(name) == db.source)
+            .collect();
+        if names.len() < 2 {
+            continue;
+        }
+        let picked = Select::new()
+            .with_prompt(format!(
+                "Keep which bookmark for {}? The others become its aliases (esc to skip)",
+                dir.display()
+            ))
+            .items(&names)
+            .default(0)
+            .interact_opt()
+            .context("failed to show the selection, interactive cleanup needs a terminal")?;
+        let Some(keep) = picked else {
+            continue;
+        };
+        for (i, name) in names.iter().enumerate() {
+            if i != keep {
+                folded.push((names[keep].clone(), name.clone()));
+            }
+        }
+    }
+    if folded.is_empty() {
+        println!("{} nothing changed", "Info:".yellow().bold());
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    for (keep, name) in &folded {
+        let other = db.bookmarks.remove(name).expect("grouped names exist");
+        let entry = db.bookmarks.get_mut(keep).expect("grouped names exist");
+        entry.absorb(&other);
+        if !entry.aliases.contains(name) {
+            entry.aliases.push(name.clone());
+        }
+    }
+    db::save(db)?;
+    for (keep, name) in folded {
+        println!(
+            "{} {} is now an alias of {}",
+            "Success:".green().bold(),
+            name.magenta(),
+            keep.magenta()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use crate::{
+    db::{Bookmark, Bookmarks},
+    list::Filters,
+    paths, resolve,
+    shell::{self, json_quote, quote, Shell},
+};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Toml,
+    Yaml,
+    /// Aliases that cd to each bookmark, for the shell given with `--shell`
+    ShellAliases,
+    /// Parent directories of the bookmarks, joined for `CDPATH`
+    Cdpath,
+}
+
+/// Bookmarks matching the filters with their paths rewritten. With `expand`, references
+/// to other bookmarks are followed first, for formats that need real directories.
+pub fn select(
+    bookmarks: &Bookmarks,
+    filters: &Filters,
+    rewrites: &[(String, String)],
+    expand: bool,
+) -> Bookmarks {
+    let matching = filters.apply(bookmarks);
+    let matching = match expand {
+        true => resolve::expanded(bookmarks, matching),
+        false => matching
+            .map(|(name, b)| (name.clone(), b.clone()))
+            .collect(),
+    };
+    matching
+        .into_iter()
+        .map(|(name, entry)| {
+            let path = rewrite(&entry.path, rewrites);
+            (name, Bookmark { path, ..entry })
+        })
+        .collect()
+}
+
+/// Renders the bookmarks with all their metadata. JSON and TOML use the same layout
+/// as the bookmarks file, YAML mirrors it.
+pub fn render(bookmarks: &Bookmarks, format: ExportFormat, shell: Option<Shell>) -> Result<String> {
+    let stored = BTreeMap::from([("bookmarks", bookmarks)]);
+    match format {
+        ExportFormat::Json => {
+            let mut json =
+                serde_json::to_string_pretty(&stored).context("failed to serialize bookmarks")?;
+            json.push('\n');
+            Ok(json)
+        }
+        ExportFormat::Toml => toml::to_string(&stored).context("failed to serialize bookmarks"),
+        ExportFormat::Csv => csv(bookmarks),
+        ExportFormat::Yaml => yaml(bookmarks),
+        ExportFormat::Cdpath => cdpath(bookmarks),
+        ExportFormat::ShellAliases => {
+            let shell = shell.context("--shell is required for shell aliases")?;
+            Ok(shell::aliases(shell, bookmarks))
+        }
+    }
+}
+
+/// Deduplicated parent directories in bookmark order, so `cd name` finds bookmarked children
+fn cdpath(bookmarks: &Bookmarks) -> Result<String> {
+    let mut parents: Vec<&Path> = vec![];
+    for parent in bookmarks
+        .values()
+        .filter_map(|b| Path::new(&b.path).parent())
+    {
+        if !parents.contains(&parent) {
+            parents.push(parent);
+        }
+    }
+    let joined =
+        std::env::join_paths(parents).context("a parent directory can't be used in CDPATH")?;
+    Ok(format!("{}\n", joined.to_string_lossy()))
+}
+
+/// One row per bookmark, lists are joined with `;`
+fn csv(bookmarks: &Bookmarks) -> Result<String> {
+    let mut out =
+        String::from("name,path,tags,note,created,used,hits,hosts,aliases,pinned,expires\n");
+    for (name, b) in bookmarks {
+        let time = |t: Option<chrono::DateTime<chrono::Utc>>| t.map(|t| t.to_rfc3339());
+        let fields = [
+            name.clone(),
+            b.path.clone(),
+            b.tags.join(";"),
+            b.note.clone().unwrap_or_default(),
+            time(b.created).unwrap_or_default(),
+            time(b.used).unwrap_or_default(),
+            b.hits.to_string(),
+            b.hosts.join(";"),
+            b.aliases.join(";"),
+            b.pinned.to_string(),
+            time(b.expires).unwrap_or_default(),
+        ];
+        let fields: Vec<_> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(out)
+}
+
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+fn yaml(bookmarks: &Bookmarks) -> Result<String> {
+    let mut out = String::from("bookmarks:\n");
+    for (name, b) in bookmarks {
+        writeln!(out, "  {}:", json_quote(name))?;
+        writeln!(out, "    path: {}", json_quote(&b.path))?;
+        let list = |items: &[String]| {
+            let items: Vec<_> = items.iter().map(|i| json_quote(i)).collect();
+            format!("[{}]", items.join(", "))
+        };
+        if !b.tags.is_empty() {
+            writeln!(out, "    tags: {}", list(&b.tags))?;
+        }
+        if let Some(note) = &b.note {
+            writeln!(out, "    note: {}", json_quote(note))?;
+        }
+        if let Some(created) = b.created {
+            writeln!(out, "    created: {}", json_quote(&created.to_rfc3339()))?;
+        }
+        if let Some(used) = b.used {
+            writeln!(out, "    used: {}", json_quote(&used.to_rfc3339()))?;
+        }
+        if b.hits > 0 {
+            writeln!(out, "    hits: {}", b.hits)?;
+        }
+        if !b.hosts.is_empty() {
+            writeln!(out, "    hosts: {}", list(&b.hosts))?;
+        }
+        if !b.aliases.is_empty() {
+            writeln!(out, "    aliases: {}", list(&b.aliases))?;
+        }
+        if b.pinned {
+            writeln!(out, "    pinned: true")?;
+        }
+        if let Some(expires) = b.expires {
+            writeln!(out, "    expires: {}", json_quote(&expires.to_rfc3339()))?;
+        }
+    }
+    Ok(out)
+}
+
+/// Renders a POSIX shell script that recreates every bookmark through markd itself,
+/// with its tags, note, hosts, aliases and pin. Paths under the current home directory
+/// are written as `$HOME/...` when `relative_home` is set.
+pub fn script(bookmarks: &Bookmarks, relative_home: bool) -> Result<String> {
+    let home = match relative_home {
+        true => Some(dirs::home_dir().context("failed to get home directory")?),
+        false => None,
+    };
+    let mut out = String::from(
+        "#!/bin/sh\n# Generated by `markd export --script`, run it to recreate the bookmarks below\n",
+    );
+    for name in creation_order(bookmarks) {
+        let entry = &bookmarks[name];
+        let path = match home.as_deref().and_then(|h| strip_home(&entry.path, h)) {
+            Some("") => "\"$HOME\"".to_string(),
+            Some(rest) => format!("\"$HOME\"{}", quote(rest)),
+            None => quote(&entry.path),
+        };
+        let name = quote(name);
+        // `-y` keeps `confirm = true` from stopping the script at every prompt
+        write!(out, "markd -y -a {name} -p {path}")?;
+        if !entry.tags.is_empty() {
+            write!(out, " --tag {}", quote(&entry.tags.join(",")))?;
+        }
+        if let Some(note) = &entry.note {
+            write!(out, " --note {}", quote(note))?;
+        }
+        if !entry.hosts.is_empty() {
+            write!(out, " --host {}", quote(&entry.hosts.join(",")))?;
+        }
+        writeln!(out)?;
+        if !entry.aliases.is_empty() {
+            let aliases = quote(&entry.aliases.join(","));
+            writeln!(out, "markd -y alias add {name} {aliases}")?;
+        }
+        if entry.pinned {
+            writeln!(out, "markd -y pin {name}")?;
+        }
+    }
+    Ok(out)
+}
+
+/// Bookmark names ordered so the bookmarks `@name` references point to come before
+/// the references. Whatever is left in a cycle goes last, in name order.
+fn creation_order(bookmarks: &Bookmarks) -> Vec<&String> {
+    let owner = |name: &str| {
+        bookmarks
+            .iter()
+            .find(|(n, b)| *n == name || b.aliases.iter().any(|a| a == name))
+            .map(|(n, _)| n)
+    };
+    let target = |b: &Bookmark| {
+        let reference = b.path.strip_prefix('@')?;
+        owner(reference.split('/').next().unwrap_or(reference))
+    };
+    let mut ordered: Vec<&String> = vec![];
+    let mut pending: Vec<_> = bookmarks.iter().collect();
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|(_, b)| target(b).is_none_or(|t| ordered.contains(&t)));
+        if ready.is_empty() {
+            ordered.extend(rest.into_iter().map(|(name, _)| name));
+            break;
+        }
+        ordered.extend(ready.into_iter().map(|(name, _)| name));
+        pending = rest;
+    }
+    ordered
+}
+
+pub fn parse_rewrite(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .ok_or_else(|| "expected FROM=TO".to_string())
+}
+
+fn rewrite(path: &str, rewrites: &[(String, String)]) -> String {
+    rewrites
+        .iter()
+        .find_map(|(from, to)| paths::replace_prefix(path, from, to))
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn strip_home<'a>(path: &'a str, home: &Path) -> Option<&'a str> {
+    let rest = path.strip_prefix(home.to_str()?)?;
+    (rest.is_empty() || rest.starts_with(std::path::MAIN_SEPARATOR)).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::fixtures::bookmarks, import::parse_csv};
+
+    #[test]
+    fn csv_round_trips_through_import() {
+        let mut exported = bookmarks(&[("api", "/src/api"), ("web", "/src/web, old")]);
+        let api = exported.get_mut("api").unwrap();
+        api.tags = vec!["work".into()];
+        api.note = Some("the \"main\" one".into());
+        api.pinned = true;
+        api.expires = Some(crate::db::now());
+        api.aliases = vec!["backend".into()];
+        let imported = parse_csv(&csv(&exported).unwrap()).unwrap();
+        for (name, b) in &exported {
+            assert!(imported[name].same_entry(b), "{name} changed");
+            assert_eq!(imported[name].created, b.created);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::{path::Path, process::Command};
+
+/// Branch and working tree state of a repository, like `main [ahead 1], 3 changed`.
+/// `None` when `dir` isn't the root of a git repository or git can't tell.
+pub fn status(dir: &Path) -> Option<String> {
+    if !dir.join(".git").exists() {
+        return None;
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain", "--branch"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    // `## main...origin/main [ahead 1]` or `## No commits yet on main`
+    let header = lines.next()?.strip_prefix("## ")?;
+    let (branch, tracking) = match header.split_once(' ') {
+        Some((branch, rest)) if rest.starts_with('[') => (branch, Some(rest)),
+        _ => (header, None),
+    };
+    let branch = branch.split("...").next().unwrap_or(branch);
+    let branch = branch.strip_prefix("No commits yet on ").unwrap_or(branch);
+    let changed = lines.count();
+    let mut status = branch.to_string();
+    if let Some(tracking) = tracking {
+        status.push(' ');
+        status.push_str(tracking);
+    }
+    match changed {
+        0 => status.push_str(", clean"),
+        n => status.push_str(&format!(", {n} changed")),
+    }
+    Some(status)
+}
@@ -0,0 +1,123 @@
+//! Log of the bookmarks `get` resolved, kept in `<database>.history`. It only holds
+//! the newest `history_size` entries, older ones are dropped as new ones come in.
+
+use crate::{
+    config::Config,
+    db::{self, Database},
+    new_table, print_table,
+    storage::{self, Backend},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct Visit {
+    pub name: String,
+    /// Directory printed by `get`, including any subpath
+    pub path: String,
+    pub at: DateTime<Utc>,
+}
+
+/// In-memory databases forget everything, so they keep no history either
+fn path(db: &Database) -> Option<PathBuf> {
+    match db.backend()? {
+        Backend::Memory => None,
+        _ => db.file().map(|file| db::sibling(file, "history")),
+    }
+}
+
+/// Visits oldest first. Lines that can't be parsed, like one cut short by a crash,
+/// are skipped.
+pub fn read(db: &Database) -> Result<Vec<Visit>> {
+    let Some(path) = path(db) else {
+        return Ok(vec![]);
+    };
+    let raw = match std::fs::read_to_string(&path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        raw => raw.with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    Ok(raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Appends a visit, rewriting the file without the oldest ones once it holds more
+/// than `limit`. A limit of 0 turns the history off.
+pub fn record(db: &Database, limit: usize, name: &str, path: String) -> Result<()> {
+    let Some(file) = self::path(db).filter(|_| limit > 0) else {
+        return Ok(());
+    };
+    let visit = Visit {
+        name: name.to_string(),
+        path,
+        at: db::now(),
+    };
+    let mut line = serde_json::to_string(&visit).context("failed to serialize history entry")?;
+    line.push('\n');
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut f| f.write_all(line.as_bytes()))
+        .with_context(|| format!("failed to write to {}", file.display()))
+        .and_then(|_| {
+            let visits = read(db)?;
+            match visits.len() > limit {
+                true => write(&file, &visits[visits.len() - limit..]),
+                false => Ok(()),
+            }
+        });
+    match result {
+        // Jumping works fine without history
+        Err(err) if db::is_read_only(&err) => Ok(()),
+        result => result,
+    }
+}
+
+fn write(file: &Path, visits: &[Visit]) -> Result<()> {
+    let mut raw = String::new();
+    for visit in visits {
+        raw.push_str(&serde_json::to_string(visit).context("failed to serialize history entry")?);
+        raw.push('\n');
+    }
+    storage::write_atomic(file, raw.as_bytes())
+}
+
+pub fn list(db: &Database, config: &Config) -> Result<()> {
+    let visits = read(db)?;
+    if visits.is_empty() {
+        println!("{} history is empty", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let mut table = new_table(["Name", "Path", "When"]);
+    for visit in visits.iter().rev() {
+        table.push_record([
+            visit.name.clone(),
+            visit.path.clone(),
+            config.format_time(visit.at),
+        ]);
+    }
+    print_table(table);
+    Ok(())
+}
+
+pub fn clear(db: &Database) -> Result<()> {
+    if let Some(path) = path(db) {
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("failed to remove {}", path.display()))?
+            }
+            _ => {}
+        }
+    }
+    println!("{} history cleared", "Success:".green().bold());
+    Ok(())
+}
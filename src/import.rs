@@ -0,0 +1,413 @@
+//! Importing bookmarks from files written by `markd export` or by hand.
+
+use crate::{
+    backup,
+    config::Config,
+    db::{self, parse_tag, Bookmark, Bookmarks, Database},
+    is_clip, new_table, print_table,
+    storage::{parse_json, parse_toml},
+    trash,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use colored::Colorize;
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ImportFormat {
+    Json,
+    Csv,
+    Toml,
+}
+
+impl ImportFormat {
+    fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::Json,
+            Some("csv") => Self::Csv,
+            _ => Self::Toml,
+        }
+    }
+}
+
+/// What to do with an imported bookmark whose name is already taken
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum OnConflict {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+enum Action {
+    Add,
+    Unchanged,
+    Skip,
+    Overwrite,
+    Rename(String),
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::Add => "add".green().to_string(),
+            Action::Unchanged => "unchanged".dimmed().to_string(),
+            Action::Skip => "skip (exists)".yellow().to_string(),
+            Action::Overwrite => "overwrite".red().to_string(),
+            Action::Rename(to) => format!("{} {to}", "rename to".cyan()),
+        }
+    }
+}
+
+pub fn read(path: &Path, format: Option<ImportFormat>) -> Result<Bookmarks> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let bookmarks = match format.unwrap_or_else(|| ImportFormat::detect(path)) {
+        ImportFormat::Json => parse_json(&raw),
+        ImportFormat::Csv => parse_csv(&raw),
+        ImportFormat::Toml => parse_toml(&raw),
+    };
+    let bookmarks = bookmarks.with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(bookmarks
+        .into_iter()
+        .map(|(name, b)| (name.to_lowercase(), b))
+        .filter(|(name, _)| !is_clip(name))
+        .collect())
+}
+
+/// Adds `incoming` to the database, resolving taken names with `policy`.
+/// With `dry_run` only the planned changes are shown.
+pub fn import(
+    db: &mut Database,
+    config: &Config,
+    incoming: Bookmarks,
+    policy: OnConflict,
+    dry_run: bool,
+) -> Result<()> {
+    if incoming.is_empty() {
+        bail!("no bookmarks found to import");
+    }
+    let planned = plan(db, incoming, policy);
+
+    if dry_run {
+        let mut table = new_table(["Name", "Path", "Action"]);
+        for (name, bookmark, action) in &planned {
+            table.push_record([name.as_str(), &bookmark.path, &action.describe()]);
+        }
+        print_table(table);
+        return Ok(());
+    }
+
+    let (mut added, mut skipped, mut overwritten) = (0, 0, vec![]);
+    for (name, bookmark, action) in planned {
+        match action {
+            Action::Add => {
+                db.bookmarks.insert(name, bookmark);
+                added += 1;
+            }
+            Action::Rename(to) => {
+                db.bookmarks.insert(to, bookmark);
+                added += 1;
+            }
+            Action::Overwrite => {
+                let old = db.bookmarks.insert(name.clone(), bookmark);
+                overwritten.extend(old.map(|old| (name, old)));
+            }
+            Action::Unchanged | Action::Skip => skipped += 1,
+        }
+    }
+    if added == 0 && overwritten.is_empty() {
+        println!(
+            "{} nothing to import, {skipped} bookmarks already exist",
+            "Info:".yellow().bold()
+        );
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    let replaced = overwritten.len();
+    if !overwritten.is_empty() {
+        trash::put(db, config.trash_days, overwritten)?;
+    }
+    db::save(db)?;
+    println!(
+        "{} imported {added} new bookmarks, overwrote {replaced} and skipped {skipped}",
+        "Success:".green().bold()
+    );
+    Ok(())
+}
+
+/// What importing each of `incoming` would do to the database
+fn plan(db: &Database, incoming: Bookmarks, policy: OnConflict) -> Vec<(String, Bookmark, Action)> {
+    let names: Vec<_> = incoming.keys().cloned().collect();
+    let mut planned: Vec<(String, Bookmark, Action)> = Vec::with_capacity(incoming.len());
+    for (name, mut bookmark) in incoming {
+        let action = match db.bookmarks.get(&name) {
+            None if !taken(db, &name) => Action::Add,
+            Some(existing) if existing.same_entry(&bookmark) => Action::Unchanged,
+            _ => match policy {
+                OnConflict::Skip => Action::Skip,
+                // Archived entries and other bookmarks' aliases aren't entries to replace
+                OnConflict::Overwrite
+                    if db.archived.contains_key(&name) || db.alias_of(&name).is_some() =>
+                {
+                    Action::Skip
+                }
+                OnConflict::Overwrite => Action::Overwrite,
+                OnConflict::Rename => Action::Rename(free_name(db, &name, |candidate| {
+                    names.iter().any(|n| n == candidate)
+                        || planned.iter().any(|(_, _, action)| {
+                            matches!(action, Action::Rename(to) if to == candidate)
+                        })
+                })),
+            },
+        };
+        let (added_as, owner) = match &action {
+            Action::Add => (name.as_str(), None),
+            Action::Overwrite => (name.as_str(), Some(name.as_str())),
+            Action::Rename(to) => (to.as_str(), None),
+            Action::Unchanged | Action::Skip => {
+                planned.push((name, bookmark, action));
+                continue;
+            }
+        };
+        drop_taken_aliases(db, added_as, &mut bookmark, owner, |alias| {
+            names.iter().any(|n| n == alias)
+                || planned.iter().any(|(_, b, action)| match action {
+                    Action::Rename(to) if to == alias => true,
+                    Action::Add | Action::Overwrite | Action::Rename(_) => {
+                        b.aliases.iter().any(|a| a == alias)
+                    }
+                    Action::Unchanged | Action::Skip => false,
+                })
+        });
+        planned.push((name, bookmark, action));
+    }
+    planned
+}
+
+/// Drops the aliases of `bookmark`, about to be added as `name`, that already name
+/// another bookmark or are `reserved`. The aliases of `owner`, the entry it replaces
+/// or gets merged into, can be kept.
+pub fn drop_taken_aliases(
+    db: &Database,
+    name: &str,
+    bookmark: &mut Bookmark,
+    owner: Option<&str>,
+    reserved: impl Fn(&str) -> bool,
+) {
+    let (kept, dropped): (Vec<_>, Vec<_>) = std::mem::take(&mut bookmark.aliases)
+        .into_iter()
+        .partition(|alias| {
+            let owned = owner.is_some_and(|owner| db.alias_of(alias) == Some(owner));
+            alias != name && (owned || !taken(db, alias) && !reserved(alias))
+        });
+    bookmark.aliases = kept;
+    if !dropped.is_empty() {
+        eprintln!(
+            "{} dropped aliases of {name} that are already taken: {}",
+            "Info:".yellow().bold(),
+            dropped.join(", ")
+        );
+    }
+}
+
+pub fn taken(db: &Database, name: &str) -> bool {
+    db.bookmarks.contains_key(name) || db.archived.contains_key(name) || db.alias_of(name).is_some()
+}
+
+/// The first of `name-2`, `name-3`, ... that is neither taken nor `reserved`
+pub fn free_name(db: &Database, name: &str, reserved: impl Fn(&str) -> bool) -> String {
+    (2..)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| !taken(db, candidate) && !reserved(candidate))
+        .expect("a free name exists")
+}
+
+/// Reads the layout written by `markd export --format csv`. Only the name and path
+/// columns are required, list columns are separated by `;`.
+pub fn parse_csv(raw: &str) -> Result<Bookmarks> {
+    let mut rows = csv_rows(raw)?.into_iter();
+    let header = rows.next().context("file is empty")?;
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+    let (Some(name_col), Some(path_col)) = (column("name"), column("path")) else {
+        bail!("missing `name` or `path` column");
+    };
+    let optional = [
+        "tags", "note", "created", "used", "hits", "hosts", "aliases", "pinned", "expires",
+    ]
+    .map(column);
+    let [tags, note, created, used, hits, hosts, aliases, pinned, expires] = optional;
+
+    let mut bookmarks = Bookmarks::new();
+    for (i, row) in rows.enumerate() {
+        let line = i + 2;
+        let field = |col: Option<usize>| {
+            col.and_then(|c| row.get(c))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let list = |col| -> Vec<String> {
+            field(col)
+                .map(|f| f.split(';').map(|item| item.trim().to_string()).collect())
+                .unwrap_or_default()
+        };
+        let time = |col| -> Result<Option<DateTime<Utc>>> {
+            field(col)
+                .map(|t| DateTime::parse_from_rfc3339(t).map(|t| t.to_utc()))
+                .transpose()
+                .with_context(|| format!("invalid timestamp on line {line}"))
+        };
+        let (Some(name), Some(path)) = (field(Some(name_col)), field(Some(path_col))) else {
+            bail!("missing name or path on line {line}");
+        };
+        let tags = list(tags)
+            .iter()
+            .map(|t| parse_tag(t))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow::anyhow!("{err} on line {line}"))?;
+        let bookmark = Bookmark {
+            tags,
+            note: field(note).map(String::from),
+            created: time(created)?,
+            used: time(used)?,
+            hits: field(hits)
+                .map(str::parse)
+                .transpose()
+                .with_context(|| format!("invalid hits on line {line}"))?
+                .unwrap_or(0),
+            hosts: list(hosts),
+            aliases: list(aliases),
+            pinned: field(pinned)
+                .map(str::parse)
+                .transpose()
+                .with_context(|| format!("invalid pinned on line {line}, expected true or false"))?
+                .unwrap_or(false),
+            expires: time(expires)?,
+            ..Bookmark::new(path.to_string())
+        };
+        bookmarks.insert(name.to_string(), bookmark);
+    }
+    Ok(bookmarks)
+}
+
+/// Splits CSV into rows of fields, handling quoted fields with `""` escapes and newlines
+fn csv_rows(raw: &str) -> Result<Vec<Vec<String>>> {
+    let (mut rows, mut row, mut field) = (vec![], vec![], String::new());
+    let mut chars = raw.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quoted field");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| !(row.len() == 1 && row[0].trim().is_empty()));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::fixtures::database;
+
+    fn incoming(names: &[&str]) -> Bookmarks {
+        names
+            .iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    Bookmark::new(format!("/elsewhere/{name}")),
+                )
+            })
+            .collect()
+    }
+
+    fn actions(db: &Database, incoming: Bookmarks, policy: OnConflict) -> Vec<(String, Action)> {
+        plan(db, incoming, policy)
+            .into_iter()
+            .map(|(name, _, action)| (name, action))
+            .collect()
+    }
+
+    #[test]
+    fn skip_leaves_taken_names_alone() {
+        let db = database();
+        let planned = actions(
+            &db,
+            incoming(&["api", "backend", "docs", "old"]),
+            OnConflict::Skip,
+        );
+        assert!(matches!(planned[0], (_, Action::Skip)));
+        assert!(matches!(planned[1], (_, Action::Skip)));
+        assert!(matches!(planned[2], (_, Action::Add)));
+        assert!(matches!(planned[3], (_, Action::Skip)));
+    }
+
+    #[test]
+    fn identical_entries_are_unchanged() {
+        let db = database();
+        let incoming = Bookmarks::from([("api".to_string(), db.bookmarks["api"].clone())]);
+        let planned = actions(&db, incoming, OnConflict::Overwrite);
+        assert!(matches!(planned[0], (_, Action::Unchanged)));
+    }
+
+    #[test]
+    fn overwrite_never_replaces_aliases_or_archived_entries() {
+        let db = database();
+        let planned = actions(
+            &db,
+            incoming(&["api", "backend", "old"]),
+            OnConflict::Overwrite,
+        );
+        assert!(matches!(planned[0], (_, Action::Overwrite)));
+        assert!(matches!(planned[1], (_, Action::Skip)));
+        assert!(matches!(planned[2], (_, Action::Skip)));
+    }
+
+    #[test]
+    fn rename_avoids_taken_and_incoming_names() {
+        let db = database();
+        let planned = actions(&db, incoming(&["api", "api-2", "old"]), OnConflict::Rename);
+        assert!(matches!(&planned[0], (_, Action::Rename(to)) if to == "api-3"));
+        assert!(matches!(planned[1], (_, Action::Add)));
+        assert!(matches!(&planned[2], (_, Action::Rename(to)) if to == "old-2"));
+    }
+
+    #[test]
+    fn aliases_that_are_taken_are_dropped() {
+        let db = database();
+        let mut incoming = incoming(&["api", "docs", "zz"]);
+        incoming.get_mut("api").unwrap().aliases = vec!["backend".into(), "web".into()];
+        incoming.get_mut("zz").unwrap().aliases = vec![
+            "web".into(),
+            "backend".into(),
+            "old".into(),
+            "docs".into(),
+            "z".into(),
+        ];
+        let planned = plan(&db, incoming, OnConflict::Overwrite);
+        // The replaced entry's aliases carry over to its replacement
+        assert_eq!(planned[0].1.aliases, ["backend"]);
+        assert_eq!(planned[2].1.aliases, ["z"]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Append-only log of small mutations kept next to the database, so frequent ones
+//! like usage tracking don't rewrite the whole file. It's replayed on every load and
+//! folded back into the database by the next full save or `markd compact`.
+
+use crate::db::Bookmarks;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Op {
+    /// A bookmark was jumped to
+    Use { name: String, at: DateTime<Utc> },
+}
+
+impl Op {
+    pub fn apply(&self, bookmarks: &mut Bookmarks) {
+        match self {
+            Op::Use { name, at } => {
+                if let Some(entry) = bookmarks.get_mut(name) {
+                    entry.record_use(*at);
+                }
+            }
+        }
+    }
+}
+
+pub fn append(path: &Path, op: &Op) -> Result<()> {
+    let mut line = serde_json::to_string(op).context("failed to serialize journal entry")?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("failed to write to {}", path.display()))
+}
+
+/// Applies every journaled mutation, returns how many there were. Lines that can't be
+/// parsed, like one cut short by a crash, are skipped.
+pub fn replay(path: &Path, bookmarks: &mut Bookmarks) -> Result<usize> {
+    let raw = match std::fs::read_to_string(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+        raw => raw.with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    let ops: Vec<Op> = raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    for op in &ops {
+        op.apply(bookmarks);
+    }
+    Ok(ops.len())
+}
+
+/// Empties the journal once its entries made it into the database
+pub fn clear(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Bookmark;
+
+    fn use_op(name: &str) -> Op {
+        Op::Use {
+            name: name.into(),
+            at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn replay_counts_every_readable_entry() {
+        let path = std::env::temp_dir().join(format!("markd-test-{}.journal", std::process::id()));
+        append(&path, &use_op("api")).unwrap();
+        append(&path, &use_op("api")).unwrap();
+        append(&path, &use_op("removed")).unwrap();
+        // A line cut short by a crash mid-append
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(b"{\"op\":\"use\",\"na"))
+            .unwrap();
+
+        let mut bookmarks = Bookmarks::from([("api".to_string(), Bookmark::new("/api".into()))]);
+        let replayed = replay(&path, &mut bookmarks).unwrap();
+        clear(&path).unwrap();
+        assert_eq!(replayed, 3);
+        assert_eq!(bookmarks["api"].hits, 2);
+        assert!(bookmarks["api"].used.is_some());
+        assert!(!bookmarks.contains_key("removed"));
+    }
+
+    #[test]
+    fn replay_without_journal_is_empty() {
+        let path = std::env::temp_dir().join("markd-test-missing.journal");
+        let mut bookmarks = Bookmarks::new();
+        assert_eq!(replay(&path, &mut bookmarks).unwrap(), 0);
+        assert!(!path.exists());
+    }
+}
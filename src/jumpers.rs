@@ -0,0 +1,174 @@
+//! Reading the databases of other directory jumpers (zoxide, autojump, z and fasd),
+//! so their directories can be imported as bookmarks.
+
+use crate::db::{now, Bookmark, Bookmarks};
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use clap::ValueEnum;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Jumper {
+    Zoxide,
+    Autojump,
+    Z,
+    Fasd,
+}
+
+struct Visited {
+    path: String,
+    score: f64,
+    /// Unix timestamp of the last visit, autojump doesn't keep one
+    last: Option<i64>,
+}
+
+impl Jumper {
+    /// Where the tool keeps its data, honouring the same variables it does
+    fn default_file(self) -> Result<PathBuf> {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+        let home = || dirs::home_dir().context("failed to get home directory");
+        let data = || dirs::data_dir().context("failed to get data directory");
+        Ok(match self {
+            Jumper::Zoxide => var("_ZO_DATA_DIR")
+                .map(PathBuf::from)
+                .map_or_else(|| data().map(|d| d.join("zoxide")), Ok)?
+                .join("db.zo"),
+            Jumper::Autojump => data()?.join("autojump").join("autojump.txt"),
+            Jumper::Z => {
+                var("_Z_DATA").map_or_else(|| home().map(|h| h.join(".z")), |v| Ok(v.into()))?
+            }
+            Jumper::Fasd => var("_FASD_DATA")
+                .map_or_else(|| home().map(|h| h.join(".fasd")), |v| Ok(v.into()))?,
+        })
+    }
+}
+
+/// Reads the directories known to `jumper` with at least `min_score`, named after their
+/// slugified directory names. When several directories share a name the one with the
+/// highest score wins and the others are reported and left out.
+pub fn read(jumper: Jumper, file: Option<&Path>, min_score: Option<f64>) -> Result<Bookmarks> {
+    let file = match file {
+        Some(file) => file.to_path_buf(),
+        None => jumper.default_file()?,
+    };
+    let raw = std::fs::read(&file).with_context(|| format!("failed to read {}", file.display()))?;
+    let visited = match jumper {
+        Jumper::Zoxide => parse_zoxide(&raw),
+        Jumper::Autojump => parse_autojump(&String::from_utf8_lossy(&raw)),
+        Jumper::Z | Jumper::Fasd => parse_z(&String::from_utf8_lossy(&raw)),
+    };
+    let mut visited = visited.with_context(|| format!("failed to parse {}", file.display()))?;
+    visited.retain(|v| min_score.is_none_or(|min| v.score >= min) && Path::new(&v.path).is_dir());
+    visited.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut bookmarks = Bookmarks::new();
+    for v in visited {
+        let Some(name) = Path::new(&v.path)
+            .file_name()
+            .map(|n| slugify(&n.to_string_lossy()))
+            .filter(|n| !n.is_empty())
+        else {
+            continue;
+        };
+        if let Some(winner) = bookmarks.get(&name) {
+            println!(
+                "{} skipped {}, {} is already taken by {}",
+                "Info:".yellow().bold(),
+                v.path,
+                name.magenta(),
+                winner.path
+            );
+            continue;
+        }
+        let bookmark = Bookmark {
+            used: v.last.and_then(|t| DateTime::from_timestamp(t, 0)),
+            hits: v.score.round().max(0.0) as u64,
+            created: Some(now()),
+            ..Bookmark::new(v.path)
+        };
+        bookmarks.insert(name, bookmark);
+    }
+    Ok(bookmarks)
+}
+
+/// Lowercases and replaces runs of anything but letters and digits with a single `-`
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        match c.is_alphanumeric() {
+            true => slug.push(c),
+            false if !slug.is_empty() && !slug.ends_with('-') => slug.push('-'),
+            false => {}
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// `path|rank|time` lines, used by both z and fasd
+fn parse_z(raw: &str) -> Result<Vec<Visited>> {
+    let visited = raw.lines().filter_map(|line| {
+        let mut parts = line.rsplitn(3, '|');
+        let (last, score, path) = (parts.next()?, parts.next()?, parts.next()?);
+        Some(Visited {
+            path: path.to_string(),
+            score: score.parse().ok()?,
+            last: last.parse().ok(),
+        })
+    });
+    Ok(visited.collect())
+}
+
+/// `weight<TAB>path` lines
+fn parse_autojump(raw: &str) -> Result<Vec<Visited>> {
+    let visited = raw.lines().filter_map(|line| {
+        let (score, path) = line.split_once('\t')?;
+        Some(Visited {
+            path: path.to_string(),
+            score: score.parse().ok()?,
+            last: None,
+        })
+    });
+    Ok(visited.collect())
+}
+
+/// zoxide's `db.zo` is a little endian u32 format version followed by a bincode
+/// encoded list of (path, rank, last accessed) entries
+fn parse_zoxide(raw: &[u8]) -> Result<Vec<Visited>> {
+    let mut reader = Reader(raw);
+    let version = u32::from_le_bytes(reader.take()?);
+    if version != 3 {
+        bail!("unsupported zoxide database version {version}");
+    }
+    let len = u64::from_le_bytes(reader.take()?);
+    let mut visited = Vec::new();
+    for _ in 0..len {
+        let path_len = u64::from_le_bytes(reader.take()?) as usize;
+        let path = String::from_utf8_lossy(reader.bytes(path_len)?).to_string();
+        let score = f64::from_le_bytes(reader.take()?);
+        let last = u64::from_le_bytes(reader.take()?);
+        visited.push(Visited {
+            path,
+            score,
+            last: i64::try_from(last).ok(),
+        });
+    }
+    Ok(visited)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("unexpected end of file");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().expect("length checked"))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{db::Bookmark, shell::json_quote};
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::fmt::Write;
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum LayoutFormat {
+    Tmuxinator,
+    Zellij,
+}
+
+/// Renders a session config with one window (tmuxinator) or tab (zellij) per bookmark
+pub fn render<'a>(
+    session: &str,
+    format: LayoutFormat,
+    bookmarks: impl Iterator<Item = (&'a String, &'a Bookmark)>,
+) -> Result<String> {
+    let bookmarks: Vec<_> = bookmarks.collect();
+    if bookmarks.is_empty() {
+        bail!("no bookmarks matched, nothing to lay out");
+    }
+    let mut out = String::new();
+    match format {
+        LayoutFormat::Tmuxinator => {
+            writeln!(out, "name: {}", json_quote(session))?;
+            writeln!(out, "root: {}", json_quote(&bookmarks[0].1.path))?;
+            writeln!(out, "windows:")?;
+            for (name, entry) in bookmarks {
+                writeln!(out, "  - {}:", json_quote(name))?;
+                writeln!(out, "      root: {}", json_quote(&entry.path))?;
+            }
+        }
+        LayoutFormat::Zellij => {
+            writeln!(out, "layout {{")?;
+            writeln!(out, "    default_tab_template {{")?;
+            writeln!(
+                out,
+                "        pane size=1 borderless=true {{ plugin location=\"zellij:tab-bar\"; }}"
+            )?;
+            writeln!(out, "        children")?;
+            writeln!(
+                out,
+                "        pane size=2 borderless=true {{ plugin location=\"zellij:status-bar\"; }}"
+            )?;
+            writeln!(out, "    }}")?;
+            for (name, entry) in bookmarks {
+                let (name, cwd) = (json_quote(name), json_quote(&entry.path));
+                writeln!(out, "    tab name={name} cwd={cwd} {{")?;
+                writeln!(out, "        pane")?;
+                writeln!(out, "    }}")?;
+            }
+            writeln!(out, "}}")?;
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,765 @@
+use crate::{
+    badges::Badger,
+    config::Config,
+    db::{parse_tag, Bookmark, Bookmarks, Database},
+    du, new_table, pager, render_table,
+    resolve::target,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Args, ValueEnum};
+use colored::Colorize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    Name,
+    Path,
+    /// Newest first
+    Created,
+    /// Most recently used first
+    Used,
+    /// Most visited first
+    Visits,
+    /// Most frequently and recently used first
+    Frecency,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Column {
+    Name,
+    Aliases,
+    Path,
+    Tags,
+    Note,
+    Created,
+    Used,
+    Visits,
+    /// When a temporary bookmark expires
+    Expires,
+    /// The file a bookmark comes from when several are merged
+    Source,
+    /// Badges for missing or remote directories
+    Status,
+    /// Whether the directory is still there
+    Exists,
+    /// Branch and state of git repositories
+    Git,
+    /// Disk usage of the directory
+    Size,
+}
+
+impl Column {
+    fn header(self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Aliases => "Aliases",
+            Column::Path => "Path",
+            Column::Tags => "Tags",
+            Column::Note => "Note",
+            Column::Created => "Created",
+            Column::Used => "Last used",
+            Column::Visits => "Visits",
+            Column::Expires => "Expires",
+            Column::Source => "Source",
+            Column::Status => "Status",
+            Column::Exists => "Exists",
+            Column::Git => "Git",
+            Column::Size => "Size",
+        }
+    }
+}
+
+/// Table columns in the order they're shown, written as `name,path,tags` in the config
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "String")]
+pub struct Columns(pub Vec<Column>);
+
+impl TryFrom<String> for Columns {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let columns = raw
+            .split(',')
+            .map(|c| Column::from_str(c.trim(), true).map_err(|_| format!("unknown column {c:?}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        match columns.is_empty() {
+            true => Err("expected at least one column".into()),
+            false => Ok(Self(columns)),
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStyle {
+    /// Paths as they are
+    #[default]
+    Full,
+    /// The home directory replaced with `~`
+    Home,
+    /// Like `home`, with the parent directories cut down to one letter like the fish prompt
+    Fish,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum GroupBy {
+    Tag,
+}
+
+#[derive(Clone)]
+pub enum ListFormat {
+    Table,
+    /// A table per group
+    Grouped(GroupBy),
+    Tree,
+    Plain,
+    Rofi,
+    Alfred,
+    Picker,
+    Json,
+    /// A line per bookmark laid out by the template
+    Template(Template),
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Name,
+    Path,
+    Tags,
+    Note,
+    Aliases,
+    Created,
+    Used,
+    Hits,
+}
+
+#[derive(Clone)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+/// A line layout like `{name}\t{path}`. Placeholders are `{name}`, `{path}`, `{tags}`,
+/// `{note}`, `{aliases}`, `{created}`, `{used}` and `{hits}`, `{{` and `}}` are literal
+/// braces and `\t`, `\n` and `\\` are escapes, since shells pass them on as they are.
+#[derive(Clone)]
+pub struct Template(Vec<Piece>);
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => text.extend(['\\', other]),
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder {{{name}")),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "name" => Field::Name,
+                        "path" => Field::Path,
+                        "tags" => Field::Tags,
+                        "note" => Field::Note,
+                        "aliases" => Field::Aliases,
+                        "created" => Field::Created,
+                        "used" => Field::Used,
+                        "hits" => Field::Hits,
+                        _ => return Err(format!("unknown placeholder {{{name}}}, expected one of {{name}}, {{path}}, {{tags}}, {{note}}, {{aliases}}, {{created}}, {{used}} or {{hits}}")),
+                    };
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    pieces.push(Piece::Field(field));
+                }
+                c => text.push(c),
+            }
+        }
+        pieces.push(Piece::Text(text));
+        Ok(Self(pieces))
+    }
+
+    fn render(&self, config: &Config, name: &str, bookmark: &Bookmark, path: &str) -> String {
+        let time = |t: Option<DateTime<Utc>>| t.map(|t| config.format_time(t)).unwrap_or_default();
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(Field::Name) => name.to_string(),
+                Piece::Field(Field::Path) => path.to_string(),
+                Piece::Field(Field::Tags) => bookmark.tags.join(","),
+                Piece::Field(Field::Note) => bookmark.note.clone().unwrap_or_default(),
+                Piece::Field(Field::Aliases) => bookmark.aliases.join(","),
+                Piece::Field(Field::Created) => time(bookmark.created),
+                Piece::Field(Field::Used) => time(bookmark.used),
+                Piece::Field(Field::Hits) => bookmark.hits.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Machine readable form of a bookmark, every field is always present
+#[derive(Serialize)]
+pub struct JsonEntry<'a> {
+    name: &'a str,
+    /// Where the bookmark leads, with references to other bookmarks followed
+    path: String,
+    aliases: &'a [String],
+    tags: &'a [String],
+    note: Option<&'a str>,
+    created: Option<DateTime<Utc>>,
+    used: Option<DateTime<Utc>>,
+    hits: u64,
+    expires: Option<DateTime<Utc>>,
+    exists: bool,
+}
+
+impl<'a> JsonEntry<'a> {
+    pub fn new(name: &'a str, bookmark: &'a Bookmark, path: String) -> Self {
+        Self {
+            name,
+            exists: Path::new(&path).is_dir(),
+            path,
+            aliases: &bookmark.aliases,
+            tags: &bookmark.tags,
+            note: bookmark.note.as_deref(),
+            created: bookmark.created,
+            used: bookmark.used,
+            hits: bookmark.hits,
+            expires: bookmark.expires,
+        }
+    }
+}
+
+/// Script filter output understood by both Alfred and Raycast
+#[derive(Serialize)]
+struct AlfredOutput<'a> {
+    items: Vec<AlfredItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct AlfredItem<'a> {
+    uid: &'a str,
+    title: &'a str,
+    subtitle: &'a str,
+    arg: &'a str,
+    autocomplete: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+}
+
+#[derive(Args)]
+pub struct Filters {
+    #[arg(short, long, help = "Filter by name fragment")]
+    pub filter: Option<String>,
+    #[arg(short, long, help = "Filter by starting char or fragment")]
+    pub start: Option<String>,
+    #[arg(short, long, help = "Filter by ending char or fragment")]
+    pub end: Option<String>,
+    #[arg(long, help = "Filter by regular expression, e.g. '^(api|svc)-'")]
+    pub regex: Option<Regex>,
+    #[arg(
+        short = 'x',
+        long,
+        help = "Leave out entries containing this fragment (can be repeated)"
+    )]
+    pub exclude: Vec<String>,
+    #[arg(long, help = "Apply the name filters to paths instead")]
+    pub match_path: bool,
+    #[arg(
+        long,
+        help = "Match entries passing any of the filters instead of all of them"
+    )]
+    pub any: bool,
+    #[arg(
+        long,
+        help = "Match bookmarks with any of the given tags instead of all of them"
+    )]
+    pub any_tag: bool,
+    #[arg(
+        short,
+        long = "tag",
+        value_parser = parse_tag,
+        help = "Filter by tag (can be repeated, all must match unless --any-tag)"
+    )]
+    pub tags: Vec<String>,
+}
+
+/// Zero width markers around highlighted text. Colors are only added after the table
+/// is laid out, since escape codes would count towards the width of the cells.
+const HIGHLIGHT_START: char = '\u{2063}';
+const HIGHLIGHT_END: char = '\u{2064}';
+
+fn colorize_highlights(table: &str) -> String {
+    let mut out = String::with_capacity(table.len());
+    let mut rest = table;
+    while let Some(start) = rest.find(HIGHLIGHT_START) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + HIGHLIGHT_START.len_utf8()..];
+        let end = rest.find(HIGHLIGHT_END).unwrap_or(rest.len());
+        out.push_str(&rest[..end].yellow().bold().to_string());
+        rest = rest
+            .get(end + HIGHLIGHT_END.len_utf8()..)
+            .unwrap_or_default();
+    }
+    out.push_str(rest);
+    out
+}
+
+impl Filters {
+    /// Whether an entry of `bookmarks` matches, paths are matched where references lead
+    pub fn matches(&self, bookmarks: &Bookmarks, name: &str, bookmark: &Bookmark) -> bool {
+        let text = match self.match_path {
+            true => target(bookmarks, bookmark),
+            false => name.into(),
+        };
+        let text = text.as_ref();
+        let mut given = [
+            self.filter.as_ref().map(|f| text.contains(f.as_str())),
+            self.start.as_ref().map(|s| text.starts_with(s.as_str())),
+            self.end.as_ref().map(|e| text.ends_with(e.as_str())),
+            self.regex.as_ref().map(|r| r.is_match(text)),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(match self.any_tag {
+            true => (!self.tags.is_empty()).then(|| bookmark.has_tags(&self.tags, true)),
+            false => None,
+        })
+        .chain(
+            self.tags
+                .iter()
+                .filter(|_| !self.any_tag)
+                .map(|tag| bookmark.has_tag(tag)),
+        )
+        .peekable();
+        let matched = match self.any {
+            true => given.peek().is_none() || given.any(|m| m),
+            false => given.all(|m| m),
+        };
+        // Exclusions apply either way
+        matched && !self.exclude.iter().any(|x| text.contains(x.as_str()))
+    }
+
+    /// Marks the parts of `text` the fragment filters and `--regex` matched, so they
+    /// can be colored once the table is laid out
+    fn highlight(&self, text: &str) -> String {
+        let mut ranges = vec![];
+        if let Some(filter) = self.filter.as_deref().filter(|f| !f.is_empty()) {
+            ranges.extend(text.match_indices(filter).map(|(i, m)| (i, i + m.len())));
+        }
+        if let Some(start) = self.start.as_deref().filter(|s| text.starts_with(s)) {
+            ranges.push((0, start.len()));
+        }
+        if let Some(end) = self.end.as_deref().filter(|e| text.ends_with(e)) {
+            ranges.push((text.len() - end.len(), text.len()));
+        }
+        if let Some(regex) = &self.regex {
+            ranges.extend(regex.find_iter(text).map(|m| (m.start(), m.end())));
+        }
+        ranges.retain(|(start, end)| start < end);
+        ranges.sort();
+        let mut marked = String::new();
+        let mut done = 0;
+        for (start, end) in ranges {
+            let start = start.max(done);
+            if start >= end {
+                continue;
+            }
+            marked.push_str(&text[done..start]);
+            marked.extend([HIGHLIGHT_START]);
+            marked.push_str(&text[start..end]);
+            marked.extend([HIGHLIGHT_END]);
+            done = end;
+        }
+        marked.push_str(&text[done..]);
+        marked
+    }
+
+    /// Bookmarks matching the filters, in name order
+    pub fn apply<'a>(
+        &'a self,
+        bookmarks: &'a Bookmarks,
+    ) -> impl Iterator<Item = (&'a String, &'a Bookmark)> {
+        bookmarks
+            .iter()
+            .filter(|(name, b)| self.matches(bookmarks, name, b))
+    }
+}
+
+pub fn list(
+    config: &Config,
+    db: &Database,
+    filters: Filters,
+    sort: SortBy,
+    reverse: bool,
+    format: ListFormat,
+    print0: bool,
+) -> Result<()> {
+    let mut sorted: Vec<_> = filters.apply(&db.bookmarks).collect();
+    sort_entries(&mut sorted, sort);
+    if reverse {
+        sorted.reverse();
+    }
+    pinned_first(&mut sorted);
+    render_list(config, db, &filters, sorted.into_iter(), format, print0)
+}
+
+/// Sorts entries that are in name order, ties stay in name order
+fn sort_entries(entries: &mut [(&String, &Bookmark)], sort: SortBy) {
+    // Bookmarks missing a timestamp sort last, as if they were never created or used
+    match sort {
+        SortBy::Name => {}
+        SortBy::Path => entries.sort_by_key(|(_, b)| &b.path),
+        SortBy::Created => entries.sort_by_key(|(_, b)| Reverse(b.created)),
+        SortBy::Used => entries.sort_by_key(|(_, b)| Reverse(b.used)),
+        SortBy::Visits => entries.sort_by_key(|(_, b)| Reverse(b.hits)),
+        SortBy::Frecency => {
+            let now = crate::db::now();
+            entries.sort_by(|(_, a), (_, b)| b.frecency(now).total_cmp(&a.frecency(now)));
+        }
+    }
+}
+
+/// Moves pinned bookmarks to the top, keeping the order within both groups
+fn pinned_first(entries: &mut [(&String, &Bookmark)]) {
+    entries.sort_by_key(|(_, b)| !b.pinned);
+}
+
+/// Numbers shown in the `#` column of the table, positions in the configured sort
+/// order counting from 1. They don't change with filters or `--sort`, so `get 3`
+/// always finds the same bookmark.
+pub fn numbers(bookmarks: &Bookmarks, sort: SortBy) -> Vec<&String> {
+    let mut entries: Vec<_> = bookmarks.iter().collect();
+    sort_entries(&mut entries, sort);
+    pinned_first(&mut entries);
+    entries.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Bookmark shown as number `n` in the table
+pub fn by_number(bookmarks: &Bookmarks, sort: SortBy, n: usize) -> Option<&String> {
+    numbers(bookmarks, sort).get(n.checked_sub(1)?).copied()
+}
+
+/// Lays out the bookmarks table, with columns only for the metadata some entry has
+fn table(
+    config: &Config,
+    db: &Database,
+    filters: &Filters,
+    entries: Vec<(&String, &Bookmark)>,
+) -> String {
+    let badger = Badger::new(config.ascii);
+    let mut columns = match &config.columns {
+        Some(Columns(columns)) => columns.clone(),
+        None => default_columns(db, &entries, &badger),
+    };
+    for extra in &config.extra_columns {
+        if !columns.contains(extra) {
+            // Badges stay last
+            let at = match columns.last() {
+                Some(Column::Status) => columns.len() - 1,
+                _ => columns.len(),
+            };
+            columns.insert(at, *extra);
+        }
+    }
+    let numbers: HashMap<_, _> = numbers(&db.bookmarks, config.sort)
+        .into_iter()
+        .zip(1..)
+        .collect();
+    let mut table = new_table(["#"].into_iter().chain(columns.iter().map(|c| c.header())));
+    for (name, b) in entries {
+        let number = numbers.get(name).map_or(String::new(), |n| n.to_string());
+        let record = columns.iter().map(|column| match column {
+            Column::Name if !filters.match_path => filters.highlight(name),
+            Column::Name => name.clone(),
+            Column::Aliases => b.aliases.join(", "),
+            Column::Path => {
+                let path = shorten(&b.path, config.path_style, config.max_path_width);
+                match filters.match_path {
+                    true => filters.highlight(&path),
+                    false => path,
+                }
+            }
+            Column::Tags => b.tags.join(", "),
+            Column::Note => b.note.clone().unwrap_or_default(),
+            Column::Created => b.created.map_or("-".into(), |t| config.format_time(t)),
+            Column::Used => b.used.map_or("-".into(), |t| config.format_time(t)),
+            Column::Visits => b.hits.to_string(),
+            Column::Expires => b.expires.map_or("-".into(), |t| config.format_time(t)),
+            Column::Source => db.source_of(name).label().into(),
+            Column::Status => badger.badges(b, &target(&db.bookmarks, b)),
+            Column::Exists => match Path::new(target(&db.bookmarks, b).as_ref()).is_dir() {
+                true => "yes".into(),
+                false => format!("{HIGHLIGHT_START}no{HIGHLIGHT_END}"),
+            },
+            Column::Size => match du::size(Path::new(target(&db.bookmarks, b).as_ref())) {
+                Some((bytes, true)) => du::human(bytes),
+                // Stopped early, the real size is bigger
+                Some((bytes, false)) => format!(">{}", du::human(bytes)),
+                None => "-".into(),
+            },
+            Column::Git => {
+                crate::git::status(Path::new(target(&db.bookmarks, b).as_ref())).unwrap_or_default()
+            }
+        });
+        table.push_record(std::iter::once(number).chain(record));
+    }
+    colorize_highlights(&render_table(table, false))
+}
+
+/// Path as displayed in the table, the full path is kept in every other format
+fn shorten(path: &str, style: PathStyle, max_width: Option<usize>) -> String {
+    let home = dirs::home_dir().and_then(|h| h.to_str().map(String::from));
+    let tilde = |path: &str| match home.as_deref().and_then(|h| path.strip_prefix(h)) {
+        Some(rest) if rest.is_empty() || rest.starts_with(std::path::MAIN_SEPARATOR) => {
+            format!("~{rest}")
+        }
+        _ => path.to_string(),
+    };
+    let mut short = match style {
+        PathStyle::Full => path.to_string(),
+        PathStyle::Home => tilde(path),
+        // Every directory but the last cut down to its first character, `.` prefix included
+        PathStyle::Fish => {
+            let path = tilde(path);
+            let parts: Vec<_> = path.split(std::path::MAIN_SEPARATOR).collect();
+            let last = parts.len().saturating_sub(1);
+            parts
+                .iter()
+                .enumerate()
+                .map(|(i, part)| match i == last || *part == "~" {
+                    true => part.to_string(),
+                    false => {
+                        let keep = if part.starts_with('.') { 2 } else { 1 };
+                        part.chars().take(keep).collect()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(std::path::MAIN_SEPARATOR_STR)
+        }
+    };
+    if let Some(max) = max_width.filter(|max| short.chars().count() > *max) {
+        let skip = short.chars().count() - max.saturating_sub(1);
+        short = format!("…{}", short.chars().skip(skip).collect::<String>());
+    }
+    short
+}
+
+/// Name and path, plus the metadata columns some entry has a value for
+fn default_columns(
+    db: &Database,
+    entries: &[(&String, &Bookmark)],
+    badger: &Badger,
+) -> Vec<Column> {
+    let mut columns = vec![Column::Name];
+    if entries.iter().any(|(_, b)| !b.aliases.is_empty()) {
+        columns.push(Column::Aliases);
+    }
+    columns.push(Column::Path);
+    if entries.iter().any(|(_, b)| !b.tags.is_empty()) {
+        columns.push(Column::Tags);
+    }
+    if entries.iter().any(|(_, b)| b.note.is_some()) {
+        columns.push(Column::Note);
+    }
+    if entries
+        .iter()
+        .any(|(_, b)| b.created.is_some() || b.used.is_some())
+    {
+        columns.extend([Column::Created, Column::Used]);
+    }
+    if entries.iter().any(|(_, b)| b.expires.is_some()) {
+        columns.push(Column::Expires);
+    }
+    // Only worth a column when other files were merged in
+    if !db.layered.is_empty() {
+        columns.push(Column::Source);
+    }
+    if entries
+        .iter()
+        .any(|(_, b)| !badger.badges(b, &target(&db.bookmarks, b)).is_empty())
+    {
+        columns.push(Column::Status);
+    }
+    columns
+}
+
+fn render_list<'a>(
+    config: &Config,
+    db: &Database,
+    filters: &Filters,
+    entries: impl Iterator<Item = (&'a String, &'a Bookmark)>,
+    format: ListFormat,
+    print0: bool,
+) -> Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    // Line based formats end entries with NUL instead, for `xargs -0`
+    let end = if print0 { '\0' } else { '\n' };
+    match format {
+        ListFormat::Table => {
+            let heading = "Bookmarked directories:".green().bold();
+            let table = table(config, db, filters, entries.collect());
+            pager::page(&format!("{heading}\n{table}\n"), config.pager)?;
+        }
+        ListFormat::Grouped(GroupBy::Tag) => {
+            let entries: Vec<_> = entries.collect();
+            let mut tags: Vec<_> = entries.iter().flat_map(|(_, b)| &b.tags).collect();
+            tags.sort();
+            tags.dedup();
+            let mut text = String::new();
+            for tag in tags {
+                let tagged = entries.iter().filter(|(_, b)| b.has_tag(tag)).copied();
+                let table = table(config, db, filters, tagged.collect());
+                text.push_str(&format!("{}\n{table}\n", format!("{tag}:").green().bold()));
+            }
+            let untagged: Vec<_> = entries
+                .iter()
+                .filter(|(_, b)| b.tags.is_empty())
+                .copied()
+                .collect();
+            if !untagged.is_empty() {
+                let table = table(config, db, filters, untagged);
+                text.push_str(&format!("{}\n{table}\n", "Untagged:".green().bold()));
+            }
+            pager::page(&text, config.pager)?;
+        }
+        ListFormat::Tree => {
+            let tree = crate::tree::render(&db.bookmarks, entries, config.ascii);
+            pager::page(&tree, config.pager)?;
+        }
+        ListFormat::Plain => {
+            for (name, b) in entries {
+                let path = target(&db.bookmarks, b);
+                write!(out, "{name}:{path}{end}").or_else(ignore_broken_pipe)?;
+            }
+        }
+        ListFormat::Rofi => {
+            for (name, b) in entries {
+                let path = target(&db.bookmarks, b);
+                write!(out, "{name}\t{path}{end}").or_else(ignore_broken_pipe)?;
+            }
+        }
+        ListFormat::Picker => {
+            write_picker(&mut out, &db.bookmarks, entries).or_else(ignore_broken_pipe)?
+        }
+        ListFormat::Alfred => {
+            let entries: Vec<_> = entries
+                .map(|(name, b)| (name, target(&db.bookmarks, b)))
+                .collect();
+            let items: Vec<_> = entries
+                .iter()
+                .map(|(name, path)| AlfredItem {
+                    uid: name,
+                    title: name,
+                    subtitle: path,
+                    arg: path,
+                    autocomplete: name,
+                    kind: "file",
+                })
+                .collect();
+            serde_json::to_writer(&mut out, &AlfredOutput { items })
+                .context("failed to serialize bookmarks")?;
+            writeln!(out).or_else(ignore_broken_pipe)?;
+        }
+        ListFormat::Template(template) => {
+            for (name, b) in entries {
+                let line = template.render(config, name, b, &target(&db.bookmarks, b));
+                write!(out, "{line}{end}").or_else(ignore_broken_pipe)?;
+            }
+        }
+        ListFormat::Json => {
+            let entries: Vec<_> = entries
+                .map(|(name, b)| JsonEntry::new(name, b, target(&db.bookmarks, b).into_owned()))
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &entries)
+                .context("failed to serialize bookmarks")?;
+            writeln!(out).or_else(ignore_broken_pipe)?;
+        }
+    }
+    out.flush().or_else(ignore_broken_pipe)
+}
+
+/// Writes `name<TAB>path` lines, with names padded so the paths line up in fuzzy finders
+pub fn write_picker<'a>(
+    out: &mut impl Write,
+    bookmarks: &Bookmarks,
+    entries: impl Iterator<Item = (&'a String, &'a Bookmark)>,
+) -> io::Result<()> {
+    let entries: Vec<_> = entries.collect();
+    let width = entries.iter().map(|(name, _)| name.chars().count()).max();
+    let width = width.unwrap_or_default();
+    for (name, b) in entries {
+        writeln!(out, "{name:width$}\t{}", target(bookmarks, b))?;
+    }
+    Ok(())
+}
+
+fn ignore_broken_pipe(err: io::Error) -> Result<()> {
+    match err.kind() {
+        io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(err).context("failed to write to standard output"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::fixtures::bookmarks;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[command(flatten)]
+        filters: Filters,
+    }
+
+    fn matching(bookmarks: &Bookmarks, args: &[&str]) -> Vec<String> {
+        let args = Args::parse_from(["markd"].iter().chain(args));
+        args.filters
+            .apply(bookmarks)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn match_path_follows_references() {
+        let db = bookmarks(&[
+            ("src", "/home/me/src"),
+            ("apisrc", "@api/src"),
+            ("api", "/work/api"),
+        ]);
+        assert_eq!(
+            matching(&db, &["--match-path", "--start", "/work/api"]),
+            ["api", "apisrc"]
+        );
+        assert!(matching(&db, &["--match-path", "--filter", "@api"]).is_empty());
+    }
+}
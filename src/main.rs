@@ -1,28 +1,90 @@
+mod backup;
+mod badges;
+mod clip;
+mod config;
+mod conflicts;
+mod db;
+mod diff;
+mod doctor;
+mod du;
+mod dupes;
+mod export;
+mod git;
+mod history;
+mod import;
+mod journal;
+mod jumpers;
+mod layout;
+mod list;
+mod mcp;
+mod merge;
+mod pager;
+mod paths;
+mod pattern;
+mod pick;
+mod profile;
+mod project;
+mod relocate;
+mod resolve;
+mod scan;
+mod shell;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod storage;
+mod sync;
+mod track;
+mod trash;
+mod tree;
+#[cfg(feature = "tui")]
+mod tui;
+
 use anyhow::{bail, Context, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Parser, Subcommand};
 use colored::Colorize;
-use dirs::home_dir;
-use once_cell::sync::Lazy;
+use config::{ColorChoice, Config, TableStyle};
+use db::{parse_age, parse_tag, Access, Bookmark, Bookmarks, Database};
+use dialoguer::MultiSelect;
+use export::ExportFormat;
+use import::{ImportFormat, OnConflict};
+use jumpers::Jumper;
+use layout::LayoutFormat;
+use list::{Column, Columns, Filters, GroupBy, ListFormat, PathStyle, SortBy, Template};
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use shell::Shell;
 use std::{
-    collections::HashMap,
-    fs::OpenOptions,
-    io::{self, Read},
-    panic::PanicInfo,
+    cmp::Reverse,
+    collections::BTreeMap,
+    io,
+    panic::PanicHookInfo,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 use tabled::{builder::Builder, settings::Style};
 
-static DB_PATH: Lazy<PathBuf> = Lazy::new(db_path);
-const CLIPNAME: &str = "markd-temp";
-const ZSH_BASH: &str = r"goto() {
-    cd $(markd g $1);
-}";
-const FISH: &str = r"function goto
-    cd $(markd g $argv)
-end";
-const POWERSHELL: &str = r"function goto([string]$Bookmark) {
-    cd (markd g $Bookmark)
-}";
+static CLIPNAME: OnceCell<String> = OnceCell::new();
+static TABLE_STYLE: OnceCell<TableStyle> = OnceCell::new();
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Name older versions saved the `clip` entry under in the bookmarks, set with the
+/// `clip_name` config option
+fn clipname() -> &'static str {
+    CLIPNAME.get().map_or("markd-temp", String::as_str)
+}
+
+/// Whether `name` is a clip entry or register left in the bookmarks by an older version
+fn is_clip(name: &str) -> bool {
+    name.strip_prefix(clipname())
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+fn parse_register(register: &str) -> Result<String, String> {
+    match !register.is_empty() && register.chars().all(|c| c.is_ascii_alphanumeric()) {
+        true => Ok(register.to_lowercase()),
+        false => Err("registers are named with letters and digits, like `a` or `2`".into()),
+    }
+}
 
 #[derive(Parser)]
 #[command(name = "Markd")]
@@ -30,10 +92,104 @@ const POWERSHELL: &str = r"function goto([string]$Bookmark) {
 #[command(version = "0.1.0")]
 #[command(about = "Bookmark directories for easy directory-hopping", long_about = None)]
 struct Cli {
+    #[arg(
+        conflicts_with = "path",
+        help = "Directory path to bookmark, or a bookmark to jump to when `shorthand_jump` is enabled"
+    )]
+    target: Option<String>,
     #[arg(long, short, help = "Optional directory path to bookmark")]
     path: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["target", "path"],
+        help = "Bookmark the previous directory, $OLDPWD, instead of the current one"
+    )]
+    prev: bool,
     #[arg(long, short, help = "Alias to use instead of dir name")]
     alias: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "alias",
+        help = "Bookmark every subdirectory of the path instead, named after each directory"
+    )]
+    children: bool,
+    #[arg(
+        long,
+        requires = "children",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..),
+        help = "How many levels down the subdirectories for --children are"
+    )]
+    depth: u8,
+    #[arg(
+        long = "match",
+        requires = "children",
+        help = "Only bookmark subdirectories whose name matches this glob, e.g. 'api-*'"
+    )]
+    pattern: Option<String>,
+    #[arg(
+        long = "tag",
+        short,
+        value_delimiter = ',',
+        value_parser = parse_tag,
+        help = "Tags to attach to the bookmark (comma separated or repeated)"
+    )]
+    tags: Vec<String>,
+    #[arg(long, short, help = "Free-form note describing the bookmark")]
+    note: Option<String>,
+    #[arg(
+        long = "host",
+        value_delimiter = ',',
+        help = "Only use the bookmark on these hosts (comma separated or repeated)"
+    )]
+    hosts: Vec<String>,
+    #[arg(
+        long,
+        value_parser = parse_age,
+        help = "Remove the bookmark after this long, e.g. 12h, 2d or 1w"
+    )]
+    ttl: Option<chrono::Duration>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "When to use colors in the output"
+    )]
+    color: Option<ColorChoice>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Border style of tables [default: rounded, or `table_style` from config]"
+    )]
+    style: Option<TableStyle>,
+    #[arg(
+        short = 'y',
+        long,
+        global = true,
+        help = "Assume yes for every confirmation prompt"
+    )]
+    yes: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Use another bookmarks file, can also be set with MARKD_DB"
+    )]
+    db: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "db",
+        value_parser = profile::parse_name,
+        help = "Use a separate set of bookmarks, can also be set with MARKD_PROFILE"
+    )]
+    profile: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Modify the project's .markd.toml instead of the global bookmarks"
+    )]
+    project: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -42,38 +198,671 @@ struct Cli {
 enum Commands {
     #[command(alias = "l", about = "List all bookmarks")]
     List {
-        #[arg(short, long, help = "Filter list by name fragment")]
-        filter: Option<String>,
-        #[arg(short, long, help = "Filter list by starting char or fragment")]
-        start: Option<String>,
-        #[arg(short, long, help = "Filter list by ending char or fragment")]
-        end: Option<String>,
-        #[arg(short, long, default_value_t = false, help = "Order list by paths")]
+        #[command(flatten)]
+        filters: Filters,
+        #[arg(
+            long,
+            value_enum,
+            help = "Order list by the given key [default: name, or `sort` from config]"
+        )]
+        sort: Option<SortBy>,
+        #[arg(
+            short,
+            long,
+            conflicts_with = "sort",
+            help = "Order list by paths (same as --sort path)"
+        )]
         path: bool,
+        #[arg(short, long, help = "Reverse the order of the list")]
+        reverse: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print plain `name:path` lines instead of a table"
+        )]
+        plain: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print `name<TAB>path` lines for rofi/wofi dmenu mode"
+        )]
+        rofi: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print Alfred/Raycast script filter JSON"
+        )]
+        alfred: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print aligned `name<TAB>path` lines for fuzzy pickers like fzf"
+        )]
+        picker: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print the bookmarks as a tree of their directories"
+        )]
+        tree: bool,
+        #[arg(
+            long,
+            group = "output",
+            help = "Print the bookmarks and their metadata as JSON"
+        )]
+        json: bool,
+        #[arg(
+            long,
+            group = "output",
+            value_parser = Template::parse,
+            help = "Print a line per bookmark following this template, e.g. '{name}\\t{path}\\t{tags}'",
+            long_help = "Print a line per bookmark following this template, e.g. '{name}\\t{path}\\t{tags}'. Placeholders are {name}, {path}, {tags}, {note}, {aliases}, {created}, {used} and {hits}. Write {{ and }} for literal braces, \\t and \\n for tabs and newlines"
+        )]
+        format: Option<Template>,
+        #[arg(
+            short = '0',
+            long,
+            conflicts_with_all = ["alfred", "picker", "tree", "json", "group_by"],
+            help = "End plain lines with NUL instead of a newline, for `xargs -0`"
+        )]
+        print0: bool,
+        #[arg(
+            long,
+            value_parser = |raw: &str| Columns::try_from(raw.to_string()),
+            help = "Table columns to show, e.g. name,path,tags,used [possible values: name, aliases, path, tags, note, created, used, visits, expires, source, status, exists, git, size]"
+        )]
+        columns: Option<Columns>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Shorten paths in the table [default: full, or `path_style` from config]"
+        )]
+        path_style: Option<PathStyle>,
+        #[arg(
+            long,
+            help = "Cut table paths longer than this many characters at the start"
+        )]
+        max_path_width: Option<usize>,
+        #[arg(
+            long,
+            conflicts_with = "output",
+            help = "Add a column with the branch and state of bookmarked git repositories"
+        )]
+        git: bool,
+        #[arg(
+            long,
+            conflicts_with = "output",
+            help = "Add a column with the disk usage of each directory, measured for up to 2s each"
+        )]
+        du: bool,
+        #[arg(
+            long,
+            help = "Print the table even when it doesn't fit in the terminal"
+        )]
+        no_pager: bool,
+        #[arg(long, help = "List archived bookmarks instead")]
+        archived: bool,
+        #[arg(
+            long,
+            value_enum,
+            conflicts_with = "output",
+            help = "Print a table per tag, untagged bookmarks last"
+        )]
+        group_by: Option<GroupBy>,
     },
     #[command(alias = "p", about = "Purge all bookmarks whose paths no longer exist")]
-    Purge,
+    Purge {
+        #[command(flatten)]
+        filters: Filters,
+        #[arg(
+            long,
+            value_parser = parse_age,
+            help = "Only purge bookmarks not used for this long, e.g. 30d, 6w or 1y"
+        )]
+        older_than: Option<chrono::Duration>,
+        #[arg(long, help = "Show what would be purged without removing anything")]
+        dry_run: bool,
+        #[arg(
+            long,
+            short,
+            conflicts_with = "dry_run",
+            help = "Pick which of the dead bookmarks to purge"
+        )]
+        interactive: bool,
+    },
     #[command(
         alias = "g",
         about = "Get bookmark's path (use with cd and command substitution)"
     )]
     Get {
-        #[arg(default_value_t = String::from(CLIPNAME))]
-        bookmark: String,
+        #[arg(help = "Bookmark to resolve [default: the clip entry]")]
+        bookmark: Option<String>,
+        #[arg(
+            long,
+            short,
+            conflicts_with = "bookmark",
+            value_parser = parse_register,
+            help = "Resolve a clip register instead"
+        )]
+        register: Option<String>,
+        #[arg(
+            short,
+            long = "tag",
+            value_parser = parse_tag,
+            help = "Only resolve bookmarks with this tag (can be repeated, all must match)"
+        )]
+        tags: Vec<String>,
+        #[arg(long, help = "Resolve bookmarks with any of the given tags instead")]
+        any_tag: bool,
+        #[arg(
+            long,
+            help = "Print the bookmark and its metadata as JSON instead of the path"
+        )]
+        json: bool,
+        #[arg(
+            short = '0',
+            long,
+            conflicts_with = "json",
+            help = "End the path with NUL, for `xargs -0`"
+        )]
+        print0: bool,
+        #[arg(
+            long,
+            help = "Don't check that a subpath appended to the bookmark exists"
+        )]
+        no_check: bool,
     },
     #[command(
         alias = "c",
         about = "Save temp entry for quick access with `markd get` or `goto`",
         long_about = "Save current or provided directory to 'markd-temp' entry for quick switching. The saved entry will be used when no bookmark name is provided to `markd get` command"
     )]
-    Clip,
+    Clip {
+        #[arg(
+            long,
+            short,
+            value_parser = parse_register,
+            help = "Named register to use instead of the default one, e.g. `a`"
+        )]
+        register: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "list",
+            help = "Print the path of the register and clear it, the most recently clipped one by default"
+        )]
+        pop: bool,
+        #[arg(long, conflicts_with = "ttl", help = "List the clipped registers")]
+        list: bool,
+        #[arg(
+            long,
+            value_parser = parse_age,
+            help = "Remove the entry after this long, e.g. 12h, 2d or 1w"
+        )]
+        ttl: Option<chrono::Duration>,
+    },
+    #[command(
+        about = "Pick a bookmark with fzf and print its path (use with cd and command substitution)"
+    )]
+    Pick {
+        #[arg(help = "Initial search query")]
+        query: Option<String>,
+    },
+    #[command(
+        about = "Print the path of a random bookmark (use with cd and command substitution)"
+    )]
+    Random {
+        #[command(flatten)]
+        filters: Filters,
+    },
+    #[command(about = "Show the most recently created bookmarks, newest first")]
+    Recent {
+        #[arg(
+            long,
+            short = 'n',
+            default_value_t = 10,
+            help = "How many bookmarks to show"
+        )]
+        limit: usize,
+    },
+    #[command(
+        about = "Show statistics like dead paths, tag counts and the most and least used bookmarks"
+    )]
+    Stats {
+        #[arg(long, help = "Print the statistics as JSON")]
+        json: bool,
+    },
+    #[command(
+        about = "Print the path of the previously visited bookmark, like `cd -` (also `get -`)"
+    )]
+    Back,
+    #[command(
+        about = "Record a visit to a directory, called by the `markd init --track` shell hook"
+    )]
+    Track { path: PathBuf },
+    #[command(about = "Show the bookmarks `get` resolved, most recent first")]
+    History {
+        #[arg(long, help = "Forget every entry")]
+        clear: bool,
+    },
+    #[command(about = "Print the names a directory is bookmarked as")]
+    Which {
+        #[arg(help = "Directory to look up, the current directory by default")]
+        path: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Look for the closest bookmarked directory above it too, printing names and paths"
+        )]
+        ancestor: bool,
+    },
+    #[cfg(feature = "tui")]
+    #[command(
+        about = "Browse and manage bookmarks interactively",
+        long_about = "Browse, search, rename, tag and delete bookmarks interactively. Pressing Enter prints the selected path, so `cd $(markd tui)` jumps to it"
+    )]
+    Tui,
+    #[command(about = "Give a bookmark other names that share its path and metadata")]
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    #[command(about = "Check the bookmarks for problems, like dead or duplicate paths")]
+    Doctor {
+        #[arg(long, help = "Fix the problems that can be fixed automatically")]
+        fix: bool,
+    },
+    #[command(
+        about = "Find bookmarks for the same directory or for directories inside other bookmarks"
+    )]
+    Dupes {
+        #[arg(
+            short,
+            long,
+            help = "Pick which bookmark to keep for each directory, the others become its aliases"
+        )]
+        interactive: bool,
+    },
+    #[command(about = "Find where the directories of dead bookmarks were moved to")]
+    Fix {
+        #[arg(help = "Bookmark to fix, all dead bookmarks by default")]
+        bookmark: Option<String>,
+        #[arg(long, help = "Show what was found without updating anything")]
+        dry_run: bool,
+    },
+    #[command(about = "Exchange the directories two bookmarks point to")]
+    Swap { bookmark: String, other: String },
+    #[command(
+        about = "Change the start of many bookmark paths at once, e.g. after a drive or home moved"
+    )]
+    Rewrite {
+        #[arg(
+            long,
+            help = "Path prefix to replace, or a regular expression with --regex"
+        )]
+        from: String,
+        #[arg(
+            long,
+            help = "Replacement, may refer to regex groups like $1 with --regex"
+        )]
+        to: String,
+        #[arg(
+            long,
+            help = "Treat --from as a regular expression matching anywhere in the path"
+        )]
+        regex: bool,
+        #[arg(long, help = "Show the new paths without changing anything")]
+        dry_run: bool,
+    },
+    #[command(about = "Put a bookmark away without deleting it")]
+    Archive { bookmark: String },
+    #[command(about = "Bring back an archived bookmark")]
+    Unarchive { bookmark: String },
+    #[command(
+        about = "Pin a bookmark, listing it first, preferring it in lookups and keeping it from purge"
+    )]
+    Pin { bookmark: String },
+    #[command(about = "Unpin a pinned bookmark")]
+    Unpin { bookmark: String },
     #[command(alias = "r", about = "Remove given directory entry from bookmarks")]
-    Remove { bookmark: String },
+    Remove {
+        #[arg(required_unless_present = "pattern")]
+        bookmark: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "bookmark",
+            help = "Remove every bookmark whose name matches this glob, e.g. 'tmp-*'"
+        )]
+        pattern: Option<String>,
+        #[arg(
+            long,
+            requires = "pattern",
+            help = "Treat the pattern as a regular expression"
+        )]
+        regex: bool,
+        #[arg(
+            long,
+            requires = "pattern",
+            help = "Show what would be removed without removing it"
+        )]
+        dry_run: bool,
+    },
+    #[command(
+        alias = "mv",
+        about = "Rename a bookmark, keeping its tags, note and usage"
+    )]
+    Rename {
+        bookmark: String,
+        new_name: String,
+        #[arg(long, short, help = "Replace an existing bookmark with the new name")]
+        force: bool,
+    },
+    #[command(
+        alias = "cp",
+        about = "Bookmark the same directory under another name, with the same tags and note"
+    )]
+    Copy {
+        bookmark: String,
+        new_name: String,
+        #[arg(long, short, help = "Replace an existing bookmark with the new name")]
+        force: bool,
+    },
+    #[command(
+        alias = "n",
+        about = "Show, set or clear the note attached to a bookmark"
+    )]
+    Note {
+        bookmark: String,
+        #[arg(conflicts_with = "clear")]
+        note: Option<String>,
+        #[arg(long, help = "Remove the bookmark's note")]
+        clear: bool,
+    },
+    #[command(alias = "t", about = "Manage bookmark tags")]
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    #[command(about = "Allow or deny the bookmarks in a project's .markd.toml")]
+    Project {
+        #[command(subcommand)]
+        action: ProjectAction,
+    },
+    #[command(about = "List bookmark profiles or copy bookmarks between them")]
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
     #[command(
         alias = "s",
         about = "Generate required config for 'goto' command shell support"
     )]
-    Shell { stype: Shell },
+    Shell {
+        stype: Shell,
+        #[arg(
+            long,
+            help = "Add `markd init` to the shell's rc file instead of printing the config"
+        )]
+        install: bool,
+        #[arg(
+            long,
+            conflicts_with = "install",
+            help = "Remove the integration added with --install from the shell's rc file"
+        )]
+        uninstall: bool,
+        #[arg(
+            long,
+            default_value = shell::DEFAULT_CMD,
+            value_parser = shell::parse_cmd,
+            help = "Name of the generated jump function"
+        )]
+        cmd: String,
+    },
+    #[command(
+        about = "Print shell integration to evaluate on startup",
+        long_about = "Print shell integration to evaluate on startup, e.g. add `eval \"$(markd init zsh)\"` to your .zshrc"
+    )]
+    Init {
+        stype: Shell,
+        #[arg(
+            long,
+            help = "Register every bookmark as a zsh named directory (cd ~name)"
+        )]
+        hash_dirs: bool,
+        #[arg(long, help = "Leave out the completion script")]
+        no_completions: bool,
+        #[arg(
+            long,
+            help = "Bind Ctrl-G to a fuzzy bookmark picker (zsh and fish, requires fzf)"
+        )]
+        widget: bool,
+        #[arg(
+            long,
+            help = "Record every directory change, so ambiguous bookmarks resolve to the one visited more (zsh, bash and fish)"
+        )]
+        track: bool,
+        #[arg(
+            long,
+            default_value = shell::DEFAULT_CMD,
+            value_parser = shell::parse_cmd,
+            help = "Name of the generated jump function"
+        )]
+        cmd: String,
+    },
+    #[command(
+        about = "Print completion script for markd's commands and flags",
+        long_about = "Print completion script for markd's commands and flags, e.g. `markd completions fish > ~/.config/fish/completions/markd.fish`"
+    )]
+    Completions { stype: Shell },
+    #[command(name = "_complete", hide = true)]
+    Complete,
+    #[command(about = "Generate a tmuxinator or zellij session with a window per bookmark")]
+    Layout {
+        #[arg(help = "Name of the generated session")]
+        session: String,
+        #[arg(long, value_enum)]
+        format: LayoutFormat,
+        #[command(flatten)]
+        filters: Filters,
+    },
+    #[command(
+        about = "Export bookmarks for other tools or another machine",
+        group(ArgGroup::new("kind").required(true))
+    )]
+    Export {
+        #[arg(
+            long,
+            value_enum,
+            group = "kind",
+            help = "Export bookmarks with all their metadata in this format"
+        )]
+        format: Option<ExportFormat>,
+        #[arg(
+            long,
+            value_enum,
+            required_if_eq("format", "shell-aliases"),
+            help = "Shell to write aliases for with --format shell-aliases"
+        )]
+        shell: Option<Shell>,
+        #[arg(
+            long,
+            group = "kind",
+            help = "Emit a shell script that recreates all bookmarks using markd"
+        )]
+        script: bool,
+        #[arg(long, short, help = "Write to this file instead of standard output")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        filters: Filters,
+        #[arg(
+            long,
+            requires = "script",
+            help = "Write paths inside your home directory relative to $HOME"
+        )]
+        relative_home: bool,
+        #[arg(
+            long,
+            value_name = "FROM=TO",
+            value_parser = export::parse_rewrite,
+            help = "Replace the path prefix FROM with TO (can be repeated)"
+        )]
+        rewrite: Vec<(String, String)>,
+    },
+    #[command(
+        about = "Import bookmarks from a file written by export or by hand, or from another directory jumper"
+    )]
+    Import {
+        #[arg(
+            required_unless_present = "from",
+            help = "File to import bookmarks from, overrides the jumper's default data file with --from"
+        )]
+        file: Option<PathBuf>,
+        #[arg(
+            long,
+            value_enum,
+            conflicts_with = "from",
+            help = "Format of the file, detected from its extension by default"
+        )]
+        format: Option<ImportFormat>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Import the directories known to this tool, named after the directory"
+        )]
+        from: Option<Jumper>,
+        #[arg(
+            long,
+            requires = "from",
+            help = "Only import directories with at least this score"
+        )]
+        min_score: Option<f64>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "What to do with bookmarks whose name is already taken"
+        )]
+        on_conflict: OnConflict,
+        #[arg(long, help = "Show what would be imported without changing anything")]
+        dry_run: bool,
+    },
+    #[command(
+        about = "Bookmark every git repository under a directory, named after its directory"
+    )]
+    Scan {
+        #[arg(help = "Directory to search")]
+        root: PathBuf,
+        #[arg(
+            long,
+            required = true,
+            help = "Look for git repository roots (the only kind of scan so far)"
+        )]
+        git: bool,
+        #[arg(
+            long,
+            default_value_t = 4,
+            help = "How many levels below the root to search"
+        )]
+        depth: usize,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "What to do with repositories whose name is already taken"
+        )]
+        on_conflict: OnConflict,
+        #[arg(long, help = "Show what would be bookmarked without changing anything")]
+        dry_run: bool,
+    },
+    #[command(
+        about = "Bookmark several directories at once, each named after its directory",
+        long_about = "Bookmark several directories at once, each named after its directory, e.g. `find ~/src -name Cargo.toml -exec dirname {} \\; | markd add --stdin`"
+    )]
+    Add {
+        #[arg(required_unless_present = "stdin", help = "Directories to bookmark")]
+        paths: Vec<PathBuf>,
+        #[arg(long, help = "Read the directories from standard input, one per line")]
+        stdin: bool,
+    },
+    #[command(about = "Merge another bookmarks file into the current one")]
+    Merge {
+        #[arg(help = "Bookmarks file to merge in, any storage format")]
+        file: PathBuf,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            conflicts_with = "interactive",
+            help = "What to do when a name points to a different directory in each file"
+        )]
+        on_conflict: OnConflict,
+        #[arg(long, short, help = "Ask what to do for every conflicting name")]
+        interactive: bool,
+    },
+    #[command(
+        about = "Compare bookmarks with another file, or two files with each other",
+        long_about = "Lists bookmarks only in the first database, only in the second, and names pointing to different directories in each. With a single file the current bookmarks are compared against it."
+    )]
+    Diff {
+        #[arg(help = "Bookmarks file to compare with, any storage format")]
+        file: PathBuf,
+        #[arg(help = "Compare FILE against this file instead of the current bookmarks")]
+        other: Option<PathBuf>,
+        #[arg(long, help = "Print the differences as JSON")]
+        json: bool,
+    },
+    #[command(
+        about = "Sync bookmarks through a git repository",
+        long_about = "Commits the bookmarks to a git repository and pulls and pushes them when it has an upstream. Entries changed on several machines are merged one by one instead of conflicting."
+    )]
+    Sync {
+        #[arg(
+            long,
+            help = "Git working copy to sync with, overrides sync_repo from the config"
+        )]
+        repo: Option<PathBuf>,
+    },
+    #[command(about = "Run markd as a server for other programs")]
+    Serve {
+        #[arg(
+            long,
+            required = true,
+            help = "Serve bookmark tools over the Model Context Protocol on stdio"
+        )]
+        mcp: bool,
+    },
+    #[command(about = "Back up the bookmarks, or list the existing backups")]
+    Backup {
+        #[arg(long, help = "List backups instead of taking one")]
+        list: bool,
+    },
+    #[command(
+        about = "Replace the bookmarks with a backup",
+        group(ArgGroup::new("backup").required(true))
+    )]
+    Restore {
+        #[arg(long, group = "backup", help = "Backup file to restore")]
+        from: Option<PathBuf>,
+        #[arg(long, group = "backup", help = "Restore the most recent backup")]
+        latest: bool,
+    },
+    #[command(about = "Restore the bookmarks removed or overwritten by the last command")]
+    Undo,
+    #[command(about = "List or restore removed and overwritten bookmarks")]
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    #[command(about = "Move temporary bookmarks whose time ran out to the trash")]
+    Expire,
+    #[command(about = "Fold the usage journal back into the bookmarks file")]
+    Compact,
+    #[command(
+        about = "Copy the bookmarks to a file in another format",
+        long_about = "Copy the bookmarks to a file in another format, picked by its extension: .json for JSON, .db, .sqlite or .sqlite3 for SQLite (needs the `sqlite` feature), TOML otherwise. Point `db` in the config or MARKD_DB at the new file to start using it."
+    )]
+    Convert {
+        #[arg(help = "File to write the bookmarks to")]
+        output: PathBuf,
+    },
     #[command(
         about = "Migrate old bookmarks.json to the new bookmarks.toml",
         long_about = "markd now uses TOML format for storing bookmarks instead of the old JSON format. This command helps you migrate your old bookmarks to the new file.\nNote: This command will be removed in the future releases."
@@ -81,12 +870,82 @@ enum Commands {
     Migrate,
 }
 
-#[derive(ValueEnum, Clone, Copy)]
-enum Shell {
-    Fish,
-    Zsh,
-    Bash,
-    Powershell,
+#[derive(Subcommand)]
+enum TrashAction {
+    #[command(alias = "ls", about = "List trashed bookmarks, most recent first")]
+    List,
+    #[command(about = "Restore the most recently trashed bookmark with this name")]
+    Restore { name: String },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    #[command(alias = "ls", about = "List profiles and their bookmark counts")]
+    List,
+    #[command(about = "Copy bookmarks from the active profile to another one")]
+    Copy {
+        #[arg(required = true)]
+        bookmarks: Vec<String>,
+        #[arg(long, value_parser = profile::parse_name, help = "Profile to copy to")]
+        to: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectAction {
+    #[command(
+        about = "Trust the project file as it is now, it has to be allowed again after changes"
+    )]
+    Allow {
+        #[arg(help = "Project file [default: the closest one]")]
+        file: Option<PathBuf>,
+    },
+    #[command(about = "Stop using the bookmarks in a project file")]
+    Deny {
+        #[arg(help = "Project file [default: the closest one]")]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    #[command(about = "Add tags to a bookmark")]
+    Add {
+        bookmark: String,
+        #[arg(required = true, value_delimiter = ',', value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+    #[command(alias = "remove", about = "Remove tags from a bookmark")]
+    Rm {
+        bookmark: String,
+        #[arg(required = true, value_delimiter = ',', value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+    #[command(
+        alias = "ls",
+        about = "List all tags with their bookmark counts, or the tags of one bookmark"
+    )]
+    List { bookmark: Option<String> },
+}
+
+#[derive(Subcommand)]
+enum AliasAction {
+    #[command(about = "Add aliases to a bookmark")]
+    Add {
+        bookmark: String,
+        #[arg(required = true, value_delimiter = ',')]
+        aliases: Vec<String>,
+    },
+    #[command(
+        alias = "remove",
+        about = "Remove an alias, leaving the bookmark in place"
+    )]
+    Rm { alias: String },
+    #[command(
+        alias = "ls",
+        about = "List all aliases and the bookmarks they refer to"
+    )]
+    List,
 }
 
 fn main() {
@@ -104,72 +963,758 @@ fn main() {
 
 fn run() -> Result<()> {
     std::panic::set_hook(Box::new(panic_hook));
-    let args = Cli::parse();
-    let mut bookmarks = load_bookmarks()?;
+    let mut args = Cli::parse();
+    // Runs on every `cd`, so it doesn't wait for the config or the database
+    if let Some(Commands::Track { path }) = &args.command {
+        return track::track(path);
+    }
+    // Deciding on a project file shouldn't need it to be loaded first
+    if let Some(Commands::Project { action }) = &args.command {
+        return match action {
+            ProjectAction::Allow { file } => project::allow(file.clone(), false),
+            ProjectAction::Deny { file } => project::deny(file.clone()),
+        };
+    }
+    if args.prev {
+        let prev = std::env::var_os("OLDPWD")
+            .filter(|dir| !dir.is_empty())
+            .context("$OLDPWD is not set, load `markd init` for your shell to have it exported")?;
+        args.path = Some(PathBuf::from(prev));
+    }
+    let mut config = Config::load()?;
+    args.color.unwrap_or(config.color).apply();
+    ASSUME_YES.store(args.yes || !config.confirm, Ordering::Relaxed);
+    let _ = CLIPNAME.set(config.clip_name.clone());
+    let _ = TABLE_STYLE.set(args.style.unwrap_or(config.table_style));
+    // Flags beat the environment, which beats the config file, explicit paths beat profiles
+    let db_path = args
+        .db
+        .clone()
+        .or_else(|| args.profile.as_deref().map(profile::path))
+        .or_else(|| config.db.clone())
+        .or_else(|| config.profile.as_deref().map(profile::path));
+    if let Some(path) = db_path {
+        db::set_path(path);
+    }
+    if let Some(backend) = config.storage {
+        db::set_backend(backend);
+    }
+    let active_profile = args.profile.as_deref().or(config.profile.as_deref());
+    let active_profile = active_profile.unwrap_or(profile::DEFAULT).to_string();
+    let access = access(args.command.as_ref());
+    let project_file = args.project;
+    let mut db = project::load(project_file, access)?;
+    // Expired bookmarks are cleaned up by whichever command gets to write first
+    if access == Access::Write && !matches!(args.command, Some(Commands::Expire)) {
+        expire(&mut db, &config, true)?;
+    }
+    if access == Access::Write && clip::migrate(&mut db)? {
+        db::save(&db)?;
+    }
+    db::merge_system(&mut db)?;
     if let Some(cmd) = args.command {
         match cmd {
             Commands::List {
-                filter,
-                start,
-                end,
+                filters,
+                sort,
                 path,
-            } => list(&bookmarks, Filters { filter, start, end }, path),
-            Commands::Purge => purge(&mut bookmarks)?,
-            Commands::Get { bookmark } => get(&bookmarks, &bookmark)?,
-            Commands::Clip => mark(&mut bookmarks, args.path, Some(CLIPNAME.into()))?,
-            Commands::Remove { bookmark } => remove(&mut bookmarks, &bookmark)?,
-            Commands::Shell { stype } => shell(stype),
-            Commands::Migrate => migrate()?,
+                reverse,
+                plain,
+                rofi,
+                alfred,
+                picker,
+                tree,
+                json,
+                format,
+                print0,
+                columns,
+                path_style,
+                max_path_width,
+                git,
+                du,
+                no_pager,
+                archived,
+                group_by,
+            } => {
+                if git {
+                    config.extra_columns.push(Column::Git);
+                }
+                if du {
+                    config.extra_columns.push(Column::Size);
+                }
+                config.pager &= !no_pager;
+                if columns.is_some() {
+                    config.columns = columns;
+                }
+                config.path_style = path_style.unwrap_or(config.path_style);
+                if max_path_width.is_some() {
+                    config.max_path_width = max_path_width;
+                }
+                if archived {
+                    // Only listed, never saved
+                    db.bookmarks = std::mem::take(&mut db.archived);
+                    db.layered.clear();
+                }
+                let format = match (format, group_by, plain, rofi, alfred, picker) {
+                    (Some(template), ..) => ListFormat::Template(template),
+                    (_, Some(by), ..) => ListFormat::Grouped(by),
+                    (_, _, true, ..) => ListFormat::Plain,
+                    (_, _, _, true, ..) => ListFormat::Rofi,
+                    (_, _, _, _, true, _) => ListFormat::Alfred,
+                    (.., true) => ListFormat::Picker,
+                    _ if tree => ListFormat::Tree,
+                    _ if json => ListFormat::Json,
+                    _ if config.plain || print0 => ListFormat::Plain,
+                    _ => ListFormat::Table,
+                };
+                let sort = match path {
+                    true => SortBy::Path,
+                    false => sort.unwrap_or(config.sort),
+                };
+                list::list(&config, &db, filters, sort, reverse, format, print0)?
+            }
+            Commands::Purge {
+                filters,
+                older_than,
+                dry_run,
+                interactive,
+            } => purge(&mut db, &config, &filters, older_than, dry_run, interactive)?,
+            Commands::Get {
+                bookmark,
+                register,
+                tags,
+                any_tag,
+                json,
+                print0,
+                no_check,
+            } => {
+                let output = match (json, print0) {
+                    (true, _) => PathOutput::Json,
+                    (_, true) => PathOutput::Print0,
+                    _ => PathOutput::Plain,
+                };
+                match bookmark {
+                    Some(bookmark) => get(
+                        &mut db, &config, &bookmark, &tags, any_tag, output, !no_check,
+                    )?,
+                    None => get_clip(&db, register.as_deref(), output)?,
+                }
+            }
+            Commands::Clip { list: true, .. } => clip::list(&config)?,
+            Commands::Clip {
+                register,
+                pop: true,
+                ..
+            } => {
+                let entry = clip::pop(register.as_deref())?;
+                print!("{}", resolve::expand(&db.bookmarks, &entry.path)?);
+            }
+            Commands::Clip { register, ttl, .. } => {
+                let meta = Metadata {
+                    tags: args.tags,
+                    note: args.note,
+                    hosts: args.hosts,
+                    ttl: ttl.or(args.ttl),
+                };
+                let dir = validate_or_default(args.path)?;
+                let mut entry = Bookmark::new(dir.to_string_lossy().to_string());
+                meta.apply(&mut entry);
+                clip::clip(register, entry)?
+            }
+            Commands::Pick { query } => {
+                if let Some(name) = pick::pick(&db.bookmarks, query.as_deref())? {
+                    get(&mut db, &config, &name, &[], false, PathOutput::Plain, true)?
+                }
+            }
+            Commands::Random { filters } => random(&db.bookmarks, filters)?,
+            Commands::Recent { limit } => recent(&db, &config, limit),
+            Commands::Stats { json } => stats::stats(&db, &config, json)?,
+            Commands::Back => back(&mut db, &config, PathOutput::Plain)?,
+            Commands::History { clear: false } => history::list(&db, &config)?,
+            Commands::History { clear: true } => history::clear(&db)?,
+            Commands::Track { .. } | Commands::Project { .. } => {
+                unreachable!("handled before loading the database")
+            }
+            Commands::Which { path, ancestor } => which(&db.bookmarks, path, ancestor)?,
+            #[cfg(feature = "tui")]
+            Commands::Tui => tui::run(&mut db, config.trash_days)?,
+            Commands::Remove {
+                bookmark: Some(bookmark),
+                ..
+            } => {
+                backup::auto(&db, &config)?;
+                remove(&mut db, &config, &bookmark)?
+            }
+            Commands::Remove {
+                pattern: Some(pattern),
+                regex,
+                dry_run,
+                ..
+            } => {
+                let pattern = pattern::compile(&pattern, regex)?;
+                remove_matching(&mut db, &config, &pattern, dry_run)?
+            }
+            Commands::Remove { .. } => unreachable!("clap requires a bookmark or a pattern"),
+            Commands::Rename {
+                bookmark,
+                new_name,
+                force,
+            } => rename(&mut db, &config, &bookmark, &new_name, force)?,
+            Commands::Copy {
+                bookmark,
+                new_name,
+                force,
+            } => copy(&mut db, &config, &bookmark, &new_name, force)?,
+            Commands::Doctor { fix } => doctor::doctor(&mut db, fix)?,
+            Commands::Dupes { interactive } => dupes::dupes(&mut db, &config, interactive)?,
+            Commands::Fix { bookmark, dry_run } => {
+                relocate::fix(&mut db, bookmark.as_deref(), dry_run)?
+            }
+            Commands::Swap { bookmark, other } => swap(&mut db, &bookmark, &other)?,
+            Commands::Rewrite {
+                from,
+                to,
+                regex,
+                dry_run,
+            } => {
+                if regex {
+                    let from =
+                        Regex::new(&from).with_context(|| format!("invalid pattern {from:?}"))?;
+                    let rewrite = |path: &str| from.replace(path, to.as_str()).into_owned();
+                    rewrite_paths(&mut db, &config, rewrite, dry_run)?
+                } else {
+                    let rewrite = |path: &str| {
+                        paths::replace_prefix(path, &from, &to).unwrap_or_else(|| path.into())
+                    };
+                    rewrite_paths(&mut db, &config, rewrite, dry_run)?
+                }
+            }
+            Commands::Archive { bookmark } => archive(&mut db, &bookmark)?,
+            Commands::Unarchive { bookmark } => unarchive(&mut db, &bookmark)?,
+            Commands::Pin { bookmark } => pin(&mut db, &bookmark, true)?,
+            Commands::Unpin { bookmark } => pin(&mut db, &bookmark, false)?,
+            Commands::Note {
+                bookmark,
+                note,
+                clear,
+            } => set_note(&mut db, &bookmark, note, clear)?,
+            Commands::Tag { action } => tag(&mut db, action)?,
+            Commands::Alias { action } => alias(&mut db, action)?,
+            Commands::Profile { action } => match action {
+                ProfileAction::List => profile::list(&active_profile)?,
+                ProfileAction::Copy { bookmarks, to } => {
+                    profile::copy(&db, &bookmarks, &active_profile, &to)?
+                }
+            },
+            Commands::Shell {
+                stype,
+                install,
+                uninstall,
+                cmd,
+            } => {
+                if install {
+                    shell::install(stype, &cmd)?
+                } else if uninstall {
+                    shell::uninstall(stype)?
+                } else {
+                    shell::print(stype, &cmd)
+                }
+            }
+            Commands::Init {
+                stype,
+                hash_dirs,
+                no_completions,
+                widget,
+                track,
+                cmd,
+            } => {
+                let opts = shell::InitOptions {
+                    cmd,
+                    completions: !no_completions,
+                    hash_dirs,
+                    widget,
+                    track,
+                };
+                shell::init(stype, &db.bookmarks, opts)?
+            }
+            Commands::Completions { stype } => shell::completions(stype)?,
+            Commands::Complete => complete(&db.bookmarks),
+            Commands::Layout {
+                session,
+                format,
+                filters,
+            } => {
+                let matching = resolve::expanded(&db.bookmarks, filters.apply(&db.bookmarks));
+                print!("{}", layout::render(&session, format, matching.iter())?);
+            }
+            Commands::Export {
+                format,
+                shell,
+                script: _,
+                output,
+                filters,
+                relative_home,
+                rewrite,
+            } => {
+                let expand = matches!(
+                    format,
+                    Some(ExportFormat::Cdpath | ExportFormat::ShellAliases)
+                );
+                let selected = export::select(&db.bookmarks, &filters, &rewrite, expand);
+                let rendered = match format {
+                    Some(format) => export::render(&selected, format, shell)?,
+                    None => export::script(&selected, relative_home)?,
+                };
+                match output {
+                    Some(output) => {
+                        std::fs::write(&output, rendered)
+                            .with_context(|| format!("failed to write to {}", output.display()))?;
+                        println!(
+                            "{} exported {} bookmarks to {}",
+                            "Success:".green().bold(),
+                            selected.len(),
+                            output.display()
+                        );
+                    }
+                    None => print!("{rendered}"),
+                }
+            }
+            Commands::Import {
+                file,
+                format,
+                from,
+                min_score,
+                on_conflict,
+                dry_run,
+            } => {
+                let incoming = match (from, file) {
+                    (Some(jumper), file) => jumpers::read(jumper, file.as_deref(), min_score)?,
+                    (None, Some(file)) => import::read(&file, format)?,
+                    (None, None) => unreachable!("clap requires a file without --from"),
+                };
+                import::import(&mut db, &config, incoming, on_conflict, dry_run)?;
+            }
+            Commands::Scan {
+                root,
+                git: _,
+                depth,
+                on_conflict,
+                dry_run,
+            } => {
+                let found = scan::git(&root, depth)?;
+                import::import(&mut db, &config, found, on_conflict, dry_run)?;
+            }
+            Commands::Add { paths, stdin } => {
+                let mut paths: Vec<_> = paths
+                    .into_iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                if stdin {
+                    for line in io::stdin().lines() {
+                        paths.push(line.context("failed to read from standard input")?);
+                    }
+                }
+                let meta = Metadata {
+                    tags: args.tags,
+                    note: args.note,
+                    hosts: args.hosts,
+                    ttl: args.ttl,
+                };
+                add(&mut db, &config, &paths, meta)?
+            }
+            Commands::Merge {
+                file,
+                on_conflict,
+                interactive,
+            } => {
+                let theirs = merge::read(&file)?;
+                merge::merge(&mut db, &config, theirs, on_conflict, interactive)?;
+            }
+            Commands::Diff { file, other, json } => {
+                let (a, a_label) = match &other {
+                    Some(_) => (merge::read(&file)?, file.display().to_string()),
+                    None => {
+                        let label = db.file().unwrap_or(db::path()).display().to_string();
+                        (db.bookmarks.clone(), label)
+                    }
+                };
+                let b_file = other.as_ref().unwrap_or(&file);
+                let b = merge::read(b_file)?;
+                diff::Diff::new(&a, &b).print(&a_label, &b_file.display().to_string(), json)?;
+            }
+            Commands::Sync { repo } => sync::sync(&mut db, &config, repo)?,
+            Commands::Serve { mcp: _ } => mcp::serve()?,
+            Commands::Backup { list: true } => backup::list(&db, &config)?,
+            Commands::Backup { list: false } => {
+                let path = backup::create(&db, config.backup_keep)?;
+                println!(
+                    "{} bookmarks backed up to {}",
+                    "Success:".green().bold(),
+                    path.display()
+                );
+            }
+            Commands::Restore { from, latest: _ } => {
+                let from = match from {
+                    Some(from) => from,
+                    None => backup::backups(&db)?
+                        .pop()
+                        .context("no backups to restore")?,
+                };
+                backup::restore(&mut db, &config, &from)?
+            }
+            Commands::Undo => trash::undo(&mut db, config.trash_days)?,
+            Commands::Trash { action } => match action {
+                TrashAction::List => trash::list(&db, &config)?,
+                TrashAction::Restore { name } => trash::restore(&mut db, config.trash_days, &name)?,
+            },
+            Commands::Expire => expire(&mut db, &config, false)?,
+            Commands::Compact => compact(&db)?,
+            Commands::Convert { output } => convert(&db, &output)?,
+            Commands::Migrate => migrate(&mut db, &config)?,
         }
+    } else if let Some(bookmark) = shorthand_target(&config, &db.bookmarks, &args) {
+        get(
+            &mut db,
+            &config,
+            bookmark,
+            &[],
+            false,
+            PathOutput::Plain,
+            true,
+        )?;
     } else {
-        mark(&mut bookmarks, args.path, args.alias)?;
+        let path = args.path.or(args.target.map(PathBuf::from));
+        let meta = Metadata {
+            tags: args.tags,
+            note: args.note,
+            hosts: args.hosts,
+            ttl: args.ttl,
+        };
+        if args.children {
+            let pattern = args.pattern.as_deref();
+            mark_children(&mut db, &config, path, args.depth, pattern, meta)?;
+        } else {
+            mark(&mut db, &config, path, args.alias, meta)?;
+        }
+    }
+    // The project file was allowed when it was loaded, so changes made through markd
+    // are trusted too
+    if project_file && access == Access::Write {
+        project::allow(None, true)?;
     }
     Ok(())
 }
 
+/// Commands that never modify the bookmarks load them without locking or creating
+/// anything, `get` only records usage when the file can be written to
+fn access(command: Option<&Commands>) -> Access {
+    match command {
+        Some(
+            Commands::List { .. }
+            | Commands::Get { .. }
+            | Commands::Pick { .. }
+            | Commands::Random { .. }
+            | Commands::Which { .. }
+            | Commands::Recent { .. }
+            | Commands::Clip { .. }
+            | Commands::Stats { .. }
+            | Commands::History { .. }
+            | Commands::Track { .. }
+            | Commands::Project { .. }
+            | Commands::Back
+            | Commands::Profile { .. }
+            | Commands::Trash {
+                action: TrashAction::List,
+            }
+            | Commands::Shell { .. }
+            | Commands::Init { .. }
+            | Commands::Completions { .. }
+            | Commands::Complete
+            | Commands::Layout { .. }
+            | Commands::Alias {
+                action: AliasAction::List,
+            }
+            | Commands::Export { .. }
+            | Commands::Diff { .. }
+            | Commands::Import { dry_run: true, .. }
+            | Commands::Scan { dry_run: true, .. }
+            | Commands::Remove { dry_run: true, .. }
+            | Commands::Purge { dry_run: true, .. }
+            | Commands::Doctor { fix: false }
+            | Commands::Dupes { interactive: false }
+            | Commands::Fix { dry_run: true, .. }
+            | Commands::Rewrite { dry_run: true, .. }
+            | Commands::Serve { .. }
+            | Commands::Convert { .. },
+        ) => Access::Read,
+        _ => Access::Write,
+    }
+}
+
+fn shorthand_target<'a>(config: &Config, bookmarks: &Bookmarks, args: &'a Cli) -> Option<&'a str> {
+    let target = args.target.as_deref()?;
+    (config.shorthand_jump
+        && args.alias.is_none()
+        && args.tags.is_empty()
+        && args.note.is_none()
+        && args.hosts.is_empty()
+        && args.ttl.is_none()
+        && bookmarks.contains_key(target))
+    .then_some(target)
+}
+
+/// Optional details attached to a bookmark when it's created or updated
+#[derive(Clone, Default)]
+struct Metadata {
+    tags: Vec<String>,
+    note: Option<String>,
+    hosts: Vec<String>,
+    /// Makes the bookmark temporary
+    ttl: Option<chrono::Duration>,
+}
+
+impl Metadata {
+    fn apply(self, entry: &mut Bookmark) {
+        entry.add_tags(&self.tags);
+        if let Some(note) = self.note {
+            entry.note = Some(note);
+        }
+        if !self.hosts.is_empty() {
+            entry.hosts = self.hosts;
+        }
+        if let Some(ttl) = self.ttl {
+            entry.expires = db::now().checked_add_signed(ttl);
+        }
+    }
+}
+
 fn mark(
-    bookmarks: &mut HashMap<String, String>,
+    db: &mut Database,
+    config: &Config,
     path: Option<PathBuf>,
     alias: Option<String>,
+    meta: Metadata,
 ) -> Result<()> {
-    let dir = validate_or_default(path)?;
-    let path = dir.to_string_lossy().to_string();
-    let name = alias
-        .unwrap_or(
-            dir.file_name()
-                .context("couldn't get dir name")?
-                .to_string_lossy()
-                .to_string(),
-        )
-        .to_lowercase();
-
-    let msg = match bookmarks.get_mut(&name) {
-        Some(val) => {
-            if name == CLIPNAME || update() {
-                val.clear();
-                val.push_str(&path);
-                "bookmark entry updated"
+    let update =
+        || confirm("directory name already exists in bookmarks, would you like to update it?");
+    let (name, marked) = save_mark(db, config, path, alias, meta, update)?;
+    let msg = match &marked {
+        Marked::Added => "bookmarked",
+        Marked::Updated | Marked::Replaced(_) => "bookmark entry updated",
+        Marked::Unchanged => "is already bookmarked",
+        Marked::Kept => "bookmark operation cancelled",
+    };
+    let prompt = match marked {
+        Marked::Kept => "Info:".yellow().bold(),
+        _ => "Success:".green().bold(),
+    };
+    println!("{} {} {}", prompt, name.magenta(), msg);
+    if let Marked::Replaced(old) = marked {
+        print_path_change(&old, &db.bookmarks[&name].path);
+    }
+    Ok(())
+}
+
+/// What marking a directory did to the bookmark with its name
+enum Marked {
+    Added,
+    /// Same directory, with new tags or a note attached
+    Updated,
+    Unchanged,
+    /// Pointed at a new directory, the old one is given
+    Replaced(String),
+    /// The name points elsewhere and `update` declined to change it
+    Kept,
+}
+
+/// Bookmarks a directory and saves the database, returning the name it got. An existing
+/// bookmark with that name only changes directory if `update` agrees, the old entry goes
+/// to the trash.
+fn save_mark(
+    db: &mut Database,
+    config: &Config,
+    path: Option<PathBuf>,
+    alias: Option<String>,
+    meta: Metadata,
+    update: impl FnOnce() -> bool,
+) -> Result<(String, Marked)> {
+    // `@other/subdir` bookmarks a directory relative to another bookmark
+    let reference = path
+        .as_ref()
+        .and_then(|p| p.to_str())
+        .filter(|p| p.starts_with('@'))
+        .map(String::from);
+    let (path, name) = match reference {
+        Some(reference) => {
+            let dir = PathBuf::from(resolve::expand(&db.bookmarks, &reference)?);
+            if !dir.is_dir() {
+                bail!("{} is not a directory", dir.display());
+            }
+            (reference, bookmark_name(&dir, alias)?)
+        }
+        None => {
+            let dir = validate_or_default(path)?;
+            let path = dir.to_string_lossy().to_string();
+            (path, bookmark_name(&dir, alias)?)
+        }
+    };
+    if db.archived.contains_key(&name) {
+        bail!("{name} is archived, unarchive it or use another alias");
+    }
+    // Bookmarking under an alias updates the bookmark it belongs to
+    let name = match db.alias_of(&name) {
+        Some(canonical) => canonical.to_string(),
+        None => name,
+    };
+
+    let mut overwritten = None;
+    let marked = match db.bookmarks.get_mut(&name) {
+        // Marking the same directory again only attaches the given tags and note
+        Some(entry) if entry.path == path => {
+            let before = entry.clone();
+            meta.apply(entry);
+            match entry.same_entry(&before) {
+                true => Marked::Unchanged,
+                false => Marked::Updated,
+            }
+        }
+        Some(entry) => {
+            if update() {
+                overwritten = Some(entry.clone());
+                let old = std::mem::replace(&mut entry.path, path.clone());
+                meta.apply(entry);
+                Marked::Replaced(old)
             } else {
-                "bookmark operation cancelled"
+                Marked::Kept
             }
         }
         None => {
-            bookmarks.insert(name.clone(), path);
-            "bookmarked"
+            let mut entry = Bookmark::new(path.clone());
+            meta.apply(&mut entry);
+            db.bookmarks.insert(name.clone(), entry);
+            Marked::Added
         }
     };
-    save_bookmarks(&bookmarks)?;
-    let prompt = if msg.contains("cancelled") {
-        "Info:".yellow().bold()
-    } else {
+    if matches!(marked, Marked::Unchanged | Marked::Kept) {
+        return Ok((name, marked));
+    }
+    // Pointing an existing bookmark at a reference can close a cycle
+    if path.starts_with('@') {
+        resolve::expand(&db.bookmarks, &path)?;
+    }
+    if let Some(old) = overwritten {
+        trash::put(db, config.trash_days, [(name.clone(), old)])?;
+    }
+    db::save(db)?;
+    Ok((name, marked))
+}
+
+/// Bookmarks the subdirectories `depth` levels below `path` whose names match `pattern`,
+/// after showing the names they'd get. Names that are already taken, or that several
+/// of them would get, are skipped.
+fn mark_children(
+    db: &mut Database,
+    config: &Config,
+    path: Option<PathBuf>,
+    depth: u8,
+    pattern: Option<&str>,
+    meta: Metadata,
+) -> Result<()> {
+    let root = validate_or_default(path)?;
+    let pattern = pattern.map(|p| pattern::compile(p, false)).transpose()?;
+    let mut found = Bookmarks::new();
+    for dir in scan::children(&root, depth.into()) {
+        let dir_name = dir.file_name().unwrap_or_default().to_string_lossy();
+        if pattern.as_ref().is_some_and(|re| !re.is_match(&dir_name)) {
+            continue;
+        }
+        let name = bookmark_name(&dir, None)?;
+        if let Some(first) = found.get(&name) {
+            println!(
+                "{} skipped {}, {} is already taken by {}",
+                "Info:".yellow().bold(),
+                dir.display(),
+                name.magenta(),
+                first.path
+            );
+            continue;
+        }
+        let mut entry = Bookmark::new(dir.to_string_lossy().to_string());
+        meta.clone().apply(&mut entry);
+        found.insert(name, entry);
+    }
+    if found.is_empty() {
+        bail!("no matching subdirectories in {}", root.display());
+    }
+    import::import(db, config, found.clone(), OnConflict::Skip, true)?;
+    if !confirm(&format!("bookmark these {} directories?", found.len())) {
+        println!("{} bookmark operation cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    import::import(db, config, found, OnConflict::Skip, false)
+}
+
+/// Bookmarks each of `paths` under its directory name. Paths that aren't directories
+/// and names that are already taken are reported and skipped, without stopping the rest.
+fn add(db: &mut Database, config: &Config, paths: &[String], meta: Metadata) -> Result<()> {
+    let (mut added, mut skipped) = (0, 0);
+    for raw in paths.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let dir = validate_or_default(Some(PathBuf::from(raw)))
+            .and_then(|dir| Ok((bookmark_name(&dir, None)?, dir)));
+        let reason = match &dir {
+            Err(err) => Some(err.to_string()),
+            Ok((name, dir)) => match db.bookmarks.get(name) {
+                Some(b) if Path::new(&b.path) == dir => Some("already bookmarked".into()),
+                Some(b) => Some(format!("{} is already taken by {}", name.magenta(), b.path)),
+                None if import::taken(db, name) => Some(format!("{} is taken", name.magenta())),
+                None => None,
+            },
+        };
+        if let Some(reason) = reason {
+            println!("{} skipped {raw}, {reason}", "Info:".yellow().bold());
+            skipped += 1;
+            continue;
+        }
+        let (name, dir) = dir.expect("errors are skipped");
+        let mut entry = Bookmark::new(dir.to_string_lossy().to_string());
+        meta.clone().apply(&mut entry);
+        db.bookmarks.insert(name, entry);
+        added += 1;
+    }
+    if added == 0 {
+        println!(
+            "{} nothing to add, skipped {skipped} paths",
+            "Info:".yellow().bold()
+        );
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    db::save(db)?;
+    println!(
+        "{} added {added} bookmarks and skipped {skipped}",
         "Success:".green().bold()
-    };
-    println!("{} {} {}", prompt, name.magenta(), msg);
+    );
     Ok(())
 }
 
+fn print_path_change(old: &str, new: &str) {
+    println!("  {} {} {}", old.red(), "→".bold(), new.green());
+}
+
+fn bookmark_name(dir: &Path, alias: Option<String>) -> Result<String> {
+    let name = match alias {
+        Some(alias) => alias,
+        None => dir
+            .file_name()
+            .context("couldn't get dir name")?
+            .to_string_lossy()
+            .to_string(),
+    };
+    Ok(name.to_lowercase())
+}
+
 fn validate_or_default(path: Option<PathBuf>) -> Result<PathBuf> {
-    let dir = if let Some(dir) = path {
+    let dir = if let Some(dir) = path.map(paths::decode_path_arg) {
         match dir.try_exists() {
             Ok(true) => dir
                 .is_dir()
@@ -183,168 +1728,982 @@ fn validate_or_default(path: Option<PathBuf>) -> Result<PathBuf> {
     Ok(dir)
 }
 
-fn update() -> bool {
+fn confirm(question: &str) -> bool {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return true;
+    }
     println!(
-        "{} direcotry name already exists in bookmarks, would you like to update it?\n\nType y / yes to update, anything else to cancel.",
+        "{} {question}\n\nType y / yes to continue, anything else to cancel.",
         "Info:".yellow().bold(),
     );
     let mut res = String::new();
     io::stdin()
         .read_line(&mut res)
         .expect("failed to read from standard input");
-    match res.trim() {
-        "y" | "yes" => true,
-        _ => false,
+    matches!(res.trim(), "y" | "yes")
+}
+
+fn new_table<'a>(header: impl IntoIterator<Item = &'a str>) -> Builder {
+    let mut table = Builder::new();
+    table.set_header(header);
+    table
+}
+
+fn print_table(table: Builder) {
+    println!("{}", render_table(table, true));
+}
+
+/// Applies the configured style, `index` numbers the rows from 0
+fn render_table(table: Builder, index: bool) -> String {
+    let mut table = match index {
+        true => table.index().build(),
+        false => table.build(),
+    };
+    match TABLE_STYLE.get().copied().unwrap_or_default() {
+        TableStyle::Rounded => table.with(Style::rounded()),
+        TableStyle::Ascii => table.with(Style::ascii()),
+        TableStyle::Markdown => table.with(Style::markdown()),
+        TableStyle::Minimal => table.with(Style::psql()),
+        TableStyle::None => table.with(Style::blank()),
+    };
+    table.to_string()
+}
+
+/// How `get` prints the resolved bookmark
+#[derive(Clone, Copy)]
+enum PathOutput {
+    Plain,
+    /// Followed by NUL, for `xargs -0`
+    Print0,
+    Json,
+}
+
+/// Resolves and prints a bookmark's path, `-` goes back to the previously visited
+/// bookmark like `cd -`
+fn get(
+    db: &mut Database,
+    config: &Config,
+    bookmark: &str,
+    tags: &[String],
+    any_tag: bool,
+    output: PathOutput,
+    check: bool,
+) -> Result<()> {
+    if bookmark == "-" {
+        return back(db, config, output);
     }
+    let (name, subpath) = lookup(db, config, bookmark, tags, any_tag)?;
+    jump(db, config, name, &subpath, output, check)
 }
 
-struct Filters {
-    filter: Option<String>,
-    start: Option<String>,
-    end: Option<String>,
+/// Resolves user input to a bookmark name and a subpath. Anything after the first `/`
+/// is treated as a subpath to append, e.g. `proj/src/bin`, unless the whole input is a
+/// bookmark. Names, aliases, list numbers and abbreviations are all accepted.
+fn lookup(
+    db: &Database,
+    config: &Config,
+    bookmark: &str,
+    tags: &[String],
+    any_tag: bool,
+) -> Result<(String, String)> {
+    let (query, subpath) = match bookmark.split_once('/') {
+        Some((query, subpath)) if db.canonical(bookmark).is_none() => (query, subpath),
+        _ => (bookmark, ""),
+    };
+    if db.expired.contains_key(query) {
+        bail!("{query} has expired");
+    }
+    let query = match db.canonical(query) {
+        Some(name) => name,
+        None => by_number(&db.bookmarks, config.sort, query)?.unwrap_or(query),
+    };
+    // Directories visited with `cd` break ties between equally used bookmarks
+    let tracked = track::Tracked::load()?;
+    let tiebreak = |b: &Bookmark| tracked.frecency(&resolve::target(&db.bookmarks, b));
+    if tags.is_empty() {
+        let name = resolve::resolve_with(&db.bookmarks, query, tiebreak)?;
+        return Ok((name.to_string(), subpath.to_string()));
+    }
+    // Abbreviations only have to be unique among the tagged bookmarks
+    let tagged: Bookmarks = db
+        .bookmarks
+        .iter()
+        .filter(|(_, b)| b.has_tags(tags, any_tag))
+        .map(|(name, b)| (name.clone(), b.clone()))
+        .collect();
+    let name = match resolve::resolve_with(&tagged, query, tiebreak) {
+        Ok(name) => name.to_string(),
+        Err(err) => match resolve::resolve(&db.bookmarks, query) {
+            Ok(name) if !tagged.contains_key(name) => {
+                let joiner = if any_tag { " or " } else { " and " };
+                bail!("{name} is not tagged with {}", tags.join(joiner))
+            }
+            _ => return Err(err),
+        },
+    };
+    Ok((name, subpath.to_string()))
 }
 
-impl Filters {
-    fn any(&self) -> bool {
-        [&self.filter, &self.start, &self.end]
-            .iter()
-            .any(|f| f.is_some())
+/// Looks up `#3` or `3` by the number `list` shows for it. Plain numbers that are
+/// out of range are left to be resolved as a name.
+fn by_number<'a>(bookmarks: &'a Bookmarks, sort: SortBy, query: &str) -> Result<Option<&'a str>> {
+    let (digits, explicit) = match query.strip_prefix('#') {
+        Some(digits) => (digits, true),
+        None => (query, false),
+    };
+    let Ok(n) = digits.parse::<usize>() else {
+        return Ok(None);
+    };
+    match list::by_number(bookmarks, sort, n) {
+        Some(name) => Ok(Some(name)),
+        None if explicit => bail!("there is no bookmark #{n}"),
+        None => Ok(None),
     }
 }
 
-fn list(bookmarks: &HashMap<String, String>, filters: Filters, order_by_path: bool) {
-    println!("{}", "Bookmarked directories:".green().bold());
-    let mut table = new_table();
-    let mut bookmarks: Vec<_> = bookmarks.iter().collect();
-    bookmarks.sort_by_key(|(name, path)| if order_by_path { *path } else { *name });
-    if filters.any() {
-        filter_list(&mut bookmarks, filters);
+/// Jumps to the bookmark visited before the current one, so repeated calls toggle
+/// between the last two
+fn back(db: &mut Database, config: &Config, output: PathOutput) -> Result<()> {
+    let visits = history::read(db)?;
+    let current = visits
+        .last()
+        .context("history is empty, nothing to go back to")?;
+    let previous = visits
+        .iter()
+        .rev()
+        .find(|v| v.name != current.name && db.bookmarks.contains_key(&v.name))
+        .context("no other bookmark in the history to go back to")?;
+    // The bookmark may have moved since, so only the subpath is taken from the history
+    let root = resolve::expand(&db.bookmarks, &db.bookmarks[&previous.name].path)?;
+    let subpath = Path::new(&previous.path)
+        .strip_prefix(root)
+        .unwrap_or(Path::new(""))
+        .to_string_lossy()
+        .to_string();
+    jump(db, config, previous.name.clone(), &subpath, output, true)
+}
+
+/// Prints the path of a resolved bookmark, with `subpath` appended, and records the use
+fn jump(
+    db: &mut Database,
+    config: &Config,
+    name: String,
+    subpath: &str,
+    output: PathOutput,
+    check: bool,
+) -> Result<()> {
+    let entry = db.bookmarks[&name].clone();
+    let path = visit(db, config, &name, subpath, check)?;
+    print_path(&name, &entry, &path, output)
+}
+
+/// Directory of a resolved bookmark with `subpath` appended, recording the use in the
+/// bookmark's statistics and the history
+fn visit(
+    db: &mut Database,
+    config: &Config,
+    name: &str,
+    subpath: &str,
+    check: bool,
+) -> Result<PathBuf> {
+    let mut path = PathBuf::from(resolve::expand(&db.bookmarks, &db.bookmarks[name].path)?);
+    if !subpath.is_empty() {
+        path.push(subpath);
+        if check && !path.is_dir() {
+            bail!("{} is not a directory", path.display());
+        }
     }
-    bookmarks.iter().for_each(|(name, path)| {
-        table.push_record([*name, *path]);
-    });
-    print_table(table);
+    db::record_use(db, name)?;
+    let visited = path.to_string_lossy().to_string();
+    history::record(db, config.history_size, name, visited)?;
+    Ok(path)
 }
 
-#[inline]
-fn filter_list(bookmarks: &mut Vec<(&String, &String)>, filters: Filters) {
-    if let Some(filter) = filters.filter.as_ref() {
-        bookmarks.retain(|(name, _)| name.contains(filter));
+fn print_path(name: &str, entry: &Bookmark, path: &Path, output: PathOutput) -> Result<()> {
+    match output {
+        PathOutput::Plain => print!("{}", path.display()),
+        PathOutput::Print0 => print!("{}\0", path.display()),
+        PathOutput::Json => {
+            let path = path.to_string_lossy().to_string();
+            let entry = list::JsonEntry::new(name, entry, path);
+            let json =
+                serde_json::to_string_pretty(&entry).context("failed to serialize bookmark")?;
+            println!("{json}");
+        }
     }
-    if let Some(start) = filters.start.as_ref() {
-        bookmarks.retain(|(name, _)| name.starts_with(start));
+    Ok(())
+}
+
+/// Prints the path saved by `clip`, to the default register unless one is given
+fn get_clip(db: &Database, register: Option<&str>, output: PathOutput) -> Result<()> {
+    let clips = clip::Clips::load()?;
+    let entry = clips.get(register).with_context(|| match register {
+        Some(register) => format!("register {register} is empty"),
+        None => "nothing clipped, save a directory with `markd clip` first".into(),
+    })?;
+    let path = PathBuf::from(resolve::expand(&db.bookmarks, &entry.path)?);
+    print_path(register.unwrap_or(clipname()), entry, &path, output)
+}
+
+/// Bookmark names for shell completion scripts, one per line
+fn complete(bookmarks: &Bookmarks) {
+    for (name, b) in bookmarks.iter() {
+        println!("{name}");
+        for alias in &b.aliases {
+            println!("{alias}");
+        }
     }
-    if let Some(end) = filters.end.as_ref() {
-        bookmarks.retain(|(name, _)| name.ends_with(end));
+}
+
+fn random(bookmarks: &Bookmarks, filters: Filters) -> Result<()> {
+    let candidates: Vec<_> = filters
+        .apply(bookmarks)
+        .map(|(name, b)| (name, resolve::target(bookmarks, b)))
+        .filter(|(_, path)| Path::new(path.as_ref()).is_dir())
+        .collect();
+    let (_, path) = fastrand::choice(candidates).context("no bookmarks to pick from")?;
+    print!("{path}");
+    Ok(())
+}
+
+/// Prints a table of the `limit` newest bookmarks, bookmarks created before creation
+/// times were recorded are left out
+fn recent(db: &Database, config: &Config, limit: usize) {
+    let mut created: Vec<_> = db
+        .bookmarks
+        .iter()
+        .filter_map(|(name, b)| Some((b.created?, name, b)))
+        .collect();
+    if created.is_empty() {
+        println!(
+            "{} no bookmarks with a creation time",
+            "Info:".yellow().bold()
+        );
+        return;
+    }
+    created.sort_by_key(|(time, ..)| Reverse(*time));
+    let mut table = new_table(["Name", "Path", "Created"]);
+    for (time, name, b) in created.into_iter().take(limit) {
+        table.push_record([name.clone(), b.path.clone(), config.format_time(time)]);
     }
+    print_table(table);
 }
 
-fn new_table() -> Builder {
-    let mut table = Builder::new();
-    table.set_header(["Name", "Path"]);
-    table
+/// Prints every name and alias of the bookmarks pointing to `path`, failing when
+/// there are none so scripts can check if a directory is bookmarked. With `ancestor`,
+/// the closest bookmarked directory containing `path` counts as well.
+fn which(bookmarks: &Bookmarks, path: Option<PathBuf>, ancestor: bool) -> Result<()> {
+    let dir = validate_or_default(path)?;
+    let dir = dir.canonicalize().unwrap_or(dir);
+    let mut by_dir: BTreeMap<PathBuf, Vec<&String>> = BTreeMap::new();
+    for (name, b) in bookmarks.iter() {
+        let target = PathBuf::from(resolve::target(bookmarks, b).as_ref());
+        let names = by_dir
+            .entry(target.canonicalize().unwrap_or(target))
+            .or_default();
+        names.push(name);
+        names.extend(&b.aliases);
+    }
+    let found = match ancestor {
+        true => dir.ancestors().find_map(|a| by_dir.get_key_value(a)),
+        false => by_dir.get_key_value(dir.as_path()),
+    };
+    let Some((found, names)) = found else {
+        bail!("{} is not bookmarked", dir.display());
+    };
+    for name in names {
+        match ancestor {
+            true => println!("{name}\t{}", found.display()),
+            false => println!("{name}"),
+        }
+    }
+    Ok(())
 }
 
-fn print_table(table: Builder) {
-    println!("{}", table.index().build().with(Style::rounded()));
+fn remove(db: &mut Database, config: &Config, bookmark: &str) -> Result<()> {
+    if let Some(canonical) = db.alias_of(bookmark) {
+        bail!("{bookmark} is an alias of {canonical}, remove it with `markd alias rm {bookmark}`");
+    }
+    let source = db.source_of(bookmark);
+    if source != db.source {
+        bail!(
+            "{bookmark} comes from the {} bookmarks and can't be removed here",
+            source.label()
+        );
+    }
+    let entry = db
+        .bookmarks
+        .get(bookmark)
+        .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+    let mut names = vec![bookmark];
+    names.extend(entry.aliases.iter().map(String::as_str));
+    let referencing = resolve::referencing(&db.bookmarks, &names);
+    if !referencing.is_empty() {
+        bail!(
+            "{bookmark} is referenced by {}, point them elsewhere before removing it",
+            referencing.join(", ")
+        );
+    }
+    let entry = db.bookmarks.remove(bookmark).expect("checked above");
+    trash::put(db, config.trash_days, [(bookmark.to_string(), entry)])?;
+    db::save(db)?;
+    println!(
+        "{} {} removed from bookmarks",
+        "Success:".green().bold(),
+        bookmark.red(),
+    );
+    Ok(())
+}
+
+fn rename(
+    db: &mut Database,
+    config: &Config,
+    bookmark: &str,
+    new_name: &str,
+    force: bool,
+) -> Result<()> {
+    let new_name = new_name.trim().to_lowercase();
+    if new_name.is_empty() {
+        bail!("the new name can't be empty");
+    }
+    let source = db.source_of(bookmark);
+    if source != db.source {
+        bail!(
+            "{bookmark} comes from the {} bookmarks and can't be renamed here",
+            source.label()
+        );
+    }
+    if !db.bookmarks.contains_key(bookmark) {
+        bail!("{bookmark} is not in bookmarks");
+    }
+    if new_name == bookmark {
+        bail!("{bookmark} already has that name");
+    }
+    if db.archived.contains_key(&new_name) {
+        bail!("{new_name} is archived, unarchive it or use another name");
+    }
+    if let Some(canonical) = db.alias_of(&new_name) {
+        bail!("{new_name} is already an alias of {canonical}");
+    }
+    if db.bookmarks.contains_key(&new_name) && !force {
+        bail!("{new_name} already exists in bookmarks, pass --force to replace it");
+    }
+    let entry = db.bookmarks.remove(bookmark).expect("checked above");
+    if let Some(replaced) = db.bookmarks.insert(new_name.clone(), entry) {
+        trash::put(db, config.trash_days, [(new_name.clone(), replaced)])?;
+    }
+    // References move along with the entry, including the ones put aside
+    let retargeted = [&mut db.bookmarks, &mut db.archived, &mut db.expired]
+        .into_iter()
+        .map(|entries| resolve::retarget(entries, bookmark, &new_name))
+        .sum::<usize>();
+    db::save(db)?;
+    println!(
+        "{} {} renamed to {}",
+        "Success:".green().bold(),
+        bookmark.red(),
+        new_name.magenta()
+    );
+    if retargeted > 0 {
+        println!("updated the references to it in {retargeted} other bookmarks");
+    }
+    Ok(())
 }
 
-fn get(bookmarks: &HashMap<String, String>, bookmark: &str) -> Result<()> {
-    let path = bookmarks
+fn copy(
+    db: &mut Database,
+    config: &Config,
+    bookmark: &str,
+    new_name: &str,
+    force: bool,
+) -> Result<()> {
+    let new_name = new_name.trim().to_lowercase();
+    if new_name.is_empty() {
+        bail!("the new name can't be empty");
+    }
+    let original = db
+        .bookmarks
         .get(bookmark)
         .with_context(|| format!("{} is not in bookmarks", bookmark))?;
-    print!("{path}");
+    if new_name == bookmark {
+        bail!("{bookmark} already has that name");
+    }
+    if db.archived.contains_key(&new_name) {
+        bail!("{new_name} is archived, unarchive it or use another name");
+    }
+    if let Some(canonical) = db.alias_of(&new_name) {
+        bail!("{new_name} is already an alias of {canonical}");
+    }
+    if db.bookmarks.contains_key(&new_name) && !force {
+        bail!("{new_name} already exists in bookmarks, pass --force to replace it");
+    }
+    // Usage is the original's own, the copy starts fresh
+    let entry = Bookmark {
+        tags: original.tags.clone(),
+        note: original.note.clone(),
+        hosts: original.hosts.clone(),
+        ..Bookmark::new(original.path.clone())
+    };
+    if let Some(replaced) = db.bookmarks.insert(new_name.clone(), entry) {
+        trash::put(db, config.trash_days, [(new_name.clone(), replaced)])?;
+    }
+    db::save(db)?;
+    println!(
+        "{} {} copied to {}",
+        "Success:".green().bold(),
+        bookmark.magenta(),
+        new_name.magenta()
+    );
+    Ok(())
+}
+
+fn swap(db: &mut Database, bookmark: &str, other: &str) -> Result<()> {
+    if bookmark == other {
+        bail!("can't swap {bookmark} with itself");
+    }
+    for name in [bookmark, other] {
+        let source = db.source_of(name);
+        if source != db.source {
+            bail!(
+                "{name} comes from the {} bookmarks and can't be changed here",
+                source.label()
+            );
+        }
+        if !db.bookmarks.contains_key(name) {
+            bail!("{name} is not in bookmarks");
+        }
+    }
+    let path = db.bookmarks[other].path.clone();
+    let path = std::mem::replace(
+        &mut db.bookmarks.get_mut(bookmark).expect("checked").path,
+        path,
+    );
+    db.bookmarks.get_mut(other).expect("checked").path = path;
+    // A reference moved onto the bookmark it points to, e.g. `a = @b`, is a cycle now
+    for name in [bookmark, other] {
+        resolve::expand(&db.bookmarks, &db.bookmarks[name].path)?;
+    }
+    db::save(db)?;
+    println!(
+        "{} {} and {} swapped directories",
+        "Success:".green().bold(),
+        bookmark.magenta(),
+        other.magenta()
+    );
+    print_path_change(&db.bookmarks[other].path, &db.bookmarks[bookmark].path);
+    Ok(())
+}
+
+/// Swaps each path for what `rewrite` turns it into. References to other bookmarks
+/// are left alone, they follow the bookmarks they point to.
+fn rewrite_paths(
+    db: &mut Database,
+    config: &Config,
+    rewrite: impl Fn(&str) -> String,
+    dry_run: bool,
+) -> Result<()> {
+    let changes: Vec<_> = db
+        .bookmarks
+        .iter()
+        .filter(|(name, b)| !b.path.starts_with('@') && db.source_of(name) == db.source)
+        .filter_map(|(name, b)| {
+            let new = rewrite(&b.path);
+            (new != b.path).then(|| (name.clone(), new))
+        })
+        .collect();
+    if changes.is_empty() {
+        println!("{} no bookmark paths match", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let mut table = new_table(["Name", "Old path", "New path"]);
+    for (name, new) in &changes {
+        table.push_record([name, &db.bookmarks[name].path, new]);
+    }
+    if dry_run {
+        println!("{}", "Would rewrite:".yellow().bold());
+        print_table(table);
+        return Ok(());
+    }
+    print_table(table);
+    if !confirm(&format!("rewrite these {} paths?", changes.len())) {
+        println!("{} rewrite cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    let count = changes.len();
+    for (name, new) in changes {
+        db.bookmarks
+            .get_mut(&name)
+            .expect("changed names exist")
+            .path = new;
+    }
+    db::save(db)?;
+    println!("{} rewrote {count} paths", "Success:".green().bold());
     Ok(())
 }
 
-fn remove(bookmarks: &mut HashMap<String, String>, bookmark: &str) -> Result<()> {
-    bookmarks
+fn remove_matching(
+    db: &mut Database,
+    config: &Config,
+    pattern: &Regex,
+    dry_run: bool,
+) -> Result<()> {
+    let matching: Vec<_> = db
+        .bookmarks
+        .keys()
+        .filter(|name| pattern.is_match(name) && db.source_of(name) == db.source)
+        .cloned()
+        .collect();
+    if matching.is_empty() {
+        println!("{} no bookmarks match the pattern", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let mut table = new_table(["Name", "Path"]);
+    for name in &matching {
+        table.push_record([name, &db.bookmarks[name].path]);
+    }
+    if dry_run {
+        println!("{}", "Would remove:".yellow().bold());
+        print_table(table);
+        return Ok(());
+    }
+    print_table(table);
+    if !confirm(&format!("remove these {} bookmarks?", matching.len())) {
+        println!("{} remove cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    let removed: Vec<_> = matching
+        .into_iter()
+        .map(|name| {
+            let entry = db.bookmarks.remove(&name).expect("matched names exist");
+            (name, entry)
+        })
+        .collect();
+    let count = removed.len();
+    trash::put(db, config.trash_days, removed)?;
+    db::save(db)?;
+    println!("{} removed {count} bookmarks", "Success:".green().bold());
+    Ok(())
+}
+
+fn archive(db: &mut Database, bookmark: &str) -> Result<()> {
+    let source = db.source_of(bookmark);
+    if source != db.source {
+        bail!(
+            "{bookmark} comes from the {} bookmarks and can't be archived here",
+            source.label()
+        );
+    }
+    if db.archived.contains_key(bookmark) {
+        bail!("{bookmark} is already archived");
+    }
+    let mut entry = db
+        .bookmarks
         .remove(bookmark)
         .with_context(|| format!("{} is not in bookmarks", bookmark))?;
-    save_bookmarks(&bookmarks)?;
+    entry.archived = true;
+    db.archived.insert(bookmark.to_string(), entry);
+    db::save(db)?;
     println!(
-        "{} {} {}",
+        "{} {} archived",
         "Success:".green().bold(),
-        bookmark.red(),
-        "removed from bookmarks"
+        bookmark.magenta()
+    );
+    Ok(())
+}
+
+fn unarchive(db: &mut Database, bookmark: &str) -> Result<()> {
+    if db.bookmarks.contains_key(bookmark) {
+        bail!("{bookmark} already exists in bookmarks");
+    }
+    let mut entry = db
+        .archived
+        .remove(bookmark)
+        .with_context(|| format!("{} is not archived", bookmark))?;
+    entry.archived = false;
+    db.bookmarks.insert(bookmark.to_string(), entry);
+    db::save(db)?;
+    println!(
+        "{} {} is back in bookmarks",
+        "Success:".green().bold(),
+        bookmark.magenta()
     );
     Ok(())
 }
 
-fn purge(bookmarks: &mut HashMap<String, String>) -> Result<()> {
+fn pin(db: &mut Database, bookmark: &str, pinned: bool) -> Result<()> {
+    let entry = db
+        .bookmarks
+        .get_mut(bookmark)
+        .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+    match (entry.pinned, pinned) {
+        (true, true) => bail!("{bookmark} is already pinned"),
+        (false, false) => bail!("{bookmark} is not pinned"),
+        _ => entry.pinned = pinned,
+    }
+    db::save(db)?;
+    let state = if pinned { "pinned" } else { "unpinned" };
+    println!(
+        "{} {} {state}",
+        "Success:".green().bold(),
+        bookmark.magenta()
+    );
+    Ok(())
+}
+
+fn set_note(db: &mut Database, bookmark: &str, note: Option<String>, clear: bool) -> Result<()> {
+    let entry = db
+        .bookmarks
+        .get_mut(bookmark)
+        .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+    if note.is_none() && !clear {
+        if let Some(note) = &entry.note {
+            println!("{note}");
+        }
+        return Ok(());
+    }
+    entry.note = note;
+    let msg = match entry.note {
+        Some(_) => "note updated",
+        None => "note cleared",
+    };
+    db::save(db)?;
+    println!("{} {} {msg}", "Success:".green().bold(), bookmark.magenta());
+    Ok(())
+}
+
+fn alias(db: &mut Database, action: AliasAction) -> Result<()> {
+    match action {
+        AliasAction::Add { bookmark, aliases } => {
+            if !db.bookmarks.contains_key(&bookmark) {
+                bail!("{bookmark} is not in bookmarks");
+            }
+            let aliases: Vec<_> = aliases.iter().map(|a| a.trim().to_lowercase()).collect();
+            for alias in &aliases {
+                if alias.is_empty() {
+                    bail!("aliases can't be empty");
+                }
+                if db.bookmarks.contains_key(alias) || db.archived.contains_key(alias) {
+                    bail!("{alias} is already the name of a bookmark");
+                }
+                if let Some(canonical) = db.alias_of(alias).filter(|c| *c != bookmark) {
+                    bail!("{alias} is already an alias of {canonical}");
+                }
+            }
+            let entry = db.bookmarks.get_mut(&bookmark).expect("checked above");
+            let added: Vec<_> = aliases
+                .into_iter()
+                .filter(|alias| !entry.aliases.contains(alias))
+                .collect();
+            entry.aliases.extend(added.iter().cloned());
+            db::save(db)?;
+            match added.is_empty() {
+                true => println!(
+                    "{} {} already has these aliases",
+                    "Info:".yellow().bold(),
+                    bookmark.magenta()
+                ),
+                false => println!(
+                    "{} {} is now also known as {}",
+                    "Success:".green().bold(),
+                    bookmark.magenta(),
+                    added.join(", ")
+                ),
+            }
+        }
+        AliasAction::Rm { alias } => {
+            let canonical = db
+                .alias_of(&alias)
+                .with_context(|| format!("{alias} is not an alias of any bookmark"))?
+                .to_string();
+            let entry = db.bookmarks.get_mut(&canonical).expect("found above");
+            entry.aliases.retain(|a| *a != alias);
+            db::save(db)?;
+            println!(
+                "{} {} is no longer an alias of {}",
+                "Success:".green().bold(),
+                alias.red(),
+                canonical.magenta()
+            );
+        }
+        AliasAction::List => {
+            let mut table = new_table(["Alias", "Bookmark", "Path"]);
+            for (name, b) in &db.bookmarks {
+                for alias in &b.aliases {
+                    table.push_record([alias, name, &b.path]);
+                }
+            }
+            print_table(table);
+        }
+    }
+    Ok(())
+}
+
+fn tag(db: &mut Database, action: TagAction) -> Result<()> {
+    match action {
+        TagAction::Add { bookmark, tags } => {
+            let entry = db
+                .bookmarks
+                .get_mut(&bookmark)
+                .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+            let added = entry.add_tags(&tags).join(", ");
+            db::save(db)?;
+            match added.is_empty() {
+                true => println!(
+                    "{} {} already has these tags",
+                    "Info:".yellow().bold(),
+                    bookmark.magenta()
+                ),
+                false => println!(
+                    "{} {} tagged with {added}",
+                    "Success:".green().bold(),
+                    bookmark.magenta()
+                ),
+            }
+        }
+        TagAction::Rm { bookmark, tags } => {
+            let entry = db
+                .bookmarks
+                .get_mut(&bookmark)
+                .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+            let removed = entry.remove_tags(&tags).join(", ");
+            db::save(db)?;
+            match removed.is_empty() {
+                true => println!(
+                    "{} {} has none of these tags",
+                    "Info:".yellow().bold(),
+                    bookmark.magenta()
+                ),
+                false => println!(
+                    "{} {removed} removed from {}",
+                    "Success:".green().bold(),
+                    bookmark.magenta()
+                ),
+            }
+        }
+        TagAction::List {
+            bookmark: Some(bookmark),
+        } => {
+            let entry = db
+                .bookmarks
+                .get(&bookmark)
+                .with_context(|| format!("{} is not in bookmarks", bookmark))?;
+            entry.tags.iter().for_each(|tag| println!("{tag}"));
+        }
+        TagAction::List { bookmark: None } => {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for tag in db.bookmarks.values().flat_map(|b| &b.tags) {
+                *counts.entry(tag).or_default() += 1;
+            }
+            if counts.is_empty() {
+                println!("{} No tags in use", "Info:".yellow().bold());
+                return Ok(());
+            }
+            println!("{}", "Tags:".green().bold());
+            let mut table = new_table(["Tag", "Bookmarks"]);
+            for (tag, count) in counts {
+                table.push_record([tag.to_string(), count.to_string()]);
+            }
+            print_table(table);
+        }
+    }
+    Ok(())
+}
+
+fn purge(
+    db: &mut Database,
+    config: &Config,
+    filters: &Filters,
+    older_than: Option<chrono::Duration>,
+    dry_run: bool,
+    interactive: bool,
+) -> Result<()> {
+    let now = db::now();
     let mut to_remove = vec![];
-    for (name, path) in bookmarks.iter() {
-        let p: &Path = path.as_ref();
-        if !p.is_dir() {
+    for (name, entry) in filters.apply(&db.bookmarks) {
+        let path = resolve::target(&db.bookmarks, entry);
+        // Bookmarks that were never used count from when they were created
+        let stale = older_than.is_none_or(|age| {
+            entry
+                .used
+                .or(entry.created)
+                .is_none_or(|last| now - last >= age)
+        });
+        let dead = !Path::new(path.as_ref()).is_dir();
+        if dead && stale && !entry.pinned && db.source_of(name) == db.source {
             to_remove.push(name.clone());
         }
     }
     if to_remove.is_empty() {
-        return Ok(println!("{} Nothing to purge", "Info:".yellow().bold()));
+        println!("{} Nothing to purge", "Info:".yellow().bold());
+        return Ok(());
+    }
+    if dry_run {
+        println!("{}", "Would purge:".yellow().bold());
+        let mut table = new_table(["Name", "Path"]);
+        for name in &to_remove {
+            table.push_record([name, &db.bookmarks[name].path]);
+        }
+        print_table(table);
+        return Ok(());
     }
+    if interactive {
+        let items: Vec<_> = to_remove
+            .iter()
+            .map(|name| format!("{name}  {}", db.bookmarks[name].path.dimmed()))
+            .collect();
+        let picked = MultiSelect::new()
+            .with_prompt("Bookmarks to purge (space to toggle, enter to confirm)")
+            .items(&items)
+            .defaults(&vec![true; items.len()])
+            .interact_opt()
+            .context("failed to show the selection, interactive purge needs a terminal")?;
+        let Some(picked) = picked.filter(|picked| !picked.is_empty()) else {
+            println!("{} purge cancelled", "Info:".yellow().bold());
+            return Ok(());
+        };
+        to_remove = picked.into_iter().map(|i| to_remove[i].clone()).collect();
+    }
+    backup::auto(db, config)?;
     println!("{}", "Purged bookmarks:".red().bold());
-    let mut table = new_table();
-    for entry in to_remove.iter() {
-        let path = bookmarks.remove(entry).unwrap();
-        table.push_record([entry, &path]);
+    let mut table = new_table(["Name", "Path"]);
+    let mut removed = vec![];
+    for name in to_remove {
+        let entry = db.bookmarks.remove(&name).unwrap();
+        table.push_record([&name, &entry.path]);
+        removed.push((name, entry));
     }
     print_table(table);
-    save_bookmarks(bookmarks)?;
+    trash::put(db, config.trash_days, removed)?;
+    db::save(db)?;
     Ok(())
 }
 
-fn load_bookmarks() -> Result<HashMap<String, String>> {
-    let mut file = std::fs::File::options()
-        .read(true)
-        .create(true)
-        .write(true)
-        .open(DB_PATH.as_path())?;
-    let mut raw = String::new();
-    file.read_to_string(&mut raw)
-        .context("failed to read $HOME/bookmarks.toml")?;
-    Ok(toml::from_str(&raw).context("failed to parse $HOME/.bookmarks.toml")?)
-}
-
-fn save_bookmarks(bookmarks: &HashMap<String, String>) -> Result<()> {
-    let toml = toml::to_string_pretty(bookmarks).context("failed to serialize data")?;
-    std::fs::write(DB_PATH.as_path(), toml).context("failed to write to bookmarks.toml")?;
+/// Moves expired bookmarks to the trash. Runs quietly before commands that write,
+/// since their output may be read by scripts.
+fn expire(db: &mut Database, config: &Config, quiet: bool) -> Result<()> {
+    if db.expired.is_empty() {
+        if !quiet {
+            println!("{} no expired bookmarks", "Info:".yellow().bold());
+        }
+        return Ok(());
+    }
+    let expired = std::mem::take(&mut db.expired);
+    let mut table = new_table(["Name", "Path", "Expired"]);
+    for (name, b) in &expired {
+        let at = b.expires.map_or("-".into(), |t| config.format_time(t));
+        table.push_record([name.clone(), b.path.clone(), at]);
+    }
+    trash::put(db, config.trash_days, expired)?;
+    db::save(db)?;
+    if !quiet {
+        println!("{}", "Expired bookmarks:".yellow().bold());
+        print_table(table);
+    }
     Ok(())
 }
 
-fn db_path() -> PathBuf {
-    let mut home = home_dir().expect("failed to get home directory");
-    home.push("bookmarks.toml");
-    home
+fn panic_hook(info: &PanicHookInfo) {
+    eprintln!("{} {}", "Error:".red().bold(), info)
 }
 
-fn panic_hook(info: &PanicInfo) {
-    eprintln!("{} {}", "Error:".red().bold(), info)
+fn compact(db: &Database) -> Result<()> {
+    if db.journaled == 0 {
+        println!("{} nothing to compact", "Info:".yellow().bold());
+        return Ok(());
+    }
+    db::save(db)?;
+    println!(
+        "{} folded {} journal entries into {}",
+        "Success:".green().bold(),
+        db.journaled,
+        db.file().unwrap_or(db::path()).display()
+    );
+    Ok(())
 }
 
-fn shell(stype: Shell) {
+fn convert(db: &Database, output: &Path) -> Result<()> {
+    if output.exists()
+        && !confirm(&format!(
+            "{} already exists, would you like to overwrite it?",
+            output.display()
+        ))
+    {
+        return Ok(());
+    }
+    db::save_to(db, output)?;
     println!(
-        "{}",
-        match stype {
-            Shell::Fish => FISH,
-            Shell::Zsh | Shell::Bash => ZSH_BASH,
-            Shell::Powershell => POWERSHELL,
-        }
-    )
-}
-
-fn migrate() -> Result<()> {
-    let file = OpenOptions::new()
-        .read(true)
-        .create(true)
-        .write(true)
-        .open(DB_PATH.with_file_name("bookmarks.json"))
-        .context("failed to open $HOME/bookmarks.json")?;
-
-    let old_data: HashMap<String, String> =
-        serde_json::from_reader(file).context("failed to parse bookmarks.json")?;
-    let toml =
-        toml::to_string_pretty(&old_data).context("failed to convert old bookmarks to TOML")?;
-    std::fs::write(DB_PATH.as_path(), toml).context("Failed to write to bookmarks.toml")?;
+        "{} bookmarks written to {}",
+        "Success:".green().bold(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Replaces the bookmarks with the ones in the `bookmarks.json` of the first versions,
+/// asking first if there are bookmarks to lose
+fn migrate(db: &mut Database, config: &Config) -> Result<()> {
+    let file = dirs::home_dir()
+        .context("failed to get home directory")?
+        .join("bookmarks.json");
+    let raw = std::fs::read_to_string(&file).context("failed to read $HOME/bookmarks.json")?;
+    let old_data: BTreeMap<String, String> =
+        serde_json::from_str(&raw).context("failed to parse bookmarks.json")?;
+    let existing = db.own().len();
+    if existing > 0
+        && !confirm(&format!(
+            "this replaces your {existing} current bookmarks with the ones in bookmarks.json, continue?"
+        ))
+    {
+        println!("{} migration cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    backup::auto(db, config)?;
+    db.replace_own(
+        old_data
+            .into_iter()
+            .map(|(name, path)| {
+                let bookmark = Bookmark {
+                    created: None,
+                    ..Bookmark::new(path)
+                };
+                (name, bookmark)
+            })
+            .collect(),
+    );
+    db::save(db).context("failed to convert old bookmarks to TOML")?;
     println!("{} migration complete", "Success:".green().bold());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remarking_with_another_host_updates_the_bookmark() {
+        let mut db = db::fixtures::database();
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let path = dir.to_string_lossy().to_string();
+        db.bookmarks
+            .insert("tmp".into(), Bookmark::new(path.clone()));
+        let meta = Metadata {
+            hosts: vec!["otherhost".into()],
+            ..Default::default()
+        };
+        let remark = save_mark(
+            &mut db,
+            &Config::default(),
+            Some(dir),
+            Some("tmp".into()),
+            meta,
+            || false,
+        );
+        let (name, marked) = remark.unwrap();
+        assert_eq!(name, "tmp");
+        assert!(matches!(marked, Marked::Updated));
+        assert_eq!(db.bookmarks["tmp"].hosts, ["otherhost"]);
+        assert_eq!(db.bookmarks["tmp"].path, path);
+    }
+}
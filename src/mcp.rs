@@ -0,0 +1,160 @@
+//! Minimal Model Context Protocol server over stdio, exposing bookmarks as tools.
+//! Messages are newline delimited JSON-RPC 2.0, stdout is reserved for the protocol.
+
+use crate::{
+    config::Config,
+    db::{self, Access},
+    lookup, resolve, save_mark, visit, Marked, Metadata,
+};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+pub fn serve() -> Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout().lock();
+    for line in stdin.lock().lines() {
+        let line = line.context("failed to read from standard input")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = handle(&line) else {
+            continue;
+        };
+        writeln!(stdout, "{response}").context("failed to write to standard output")?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn handle(line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(req) => req,
+        Err(err) => return Some(error(Value::Null, -32700, &err.to_string())),
+    };
+    // Notifications carry no id and never get a response
+    let id = request.get("id")?.clone();
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let response = match request["method"].as_str().unwrap_or_default() {
+        "initialize" => success(
+            id,
+            json!({
+                "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "markd", "version": env!("CARGO_PKG_VERSION") },
+            }),
+        ),
+        "ping" => success(id, json!({})),
+        "tools/list" => success(id, json!({ "tools": tools() })),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let result = match call_tool(name, &params["arguments"]) {
+                Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                Err(err) => json!({
+                    "content": [{ "type": "text", "text": format!("{err:#}") }],
+                    "isError": true,
+                }),
+            };
+            success(id, result)
+        }
+        method => error(id, -32601, &format!("method not found: {method}")),
+    };
+    Some(response)
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "list_bookmarks",
+            "description": "List bookmarked directories as `name: path [tags]` lines",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "filter": { "type": "string", "description": "Only include names containing this fragment" },
+                    "tag": { "type": "string", "description": "Only include bookmarks with this tag" }
+                }
+            }
+        },
+        {
+            "name": "get_bookmark",
+            "description": "Resolve a bookmark to its directory path, accepting the same names, aliases, abbreviations and `name/subdir` paths as `markd get`",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "name": { "type": "string" } },
+                "required": ["name"]
+            }
+        },
+        {
+            "name": "add_bookmark",
+            "description": "Bookmark a directory, named after the directory unless an alias is given",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Absolute path of the directory" },
+                    "alias": { "type": "string" },
+                    "overwrite": { "type": "boolean", "description": "Point an existing bookmark with the same name at this directory, the old entry goes to the trash" }
+                },
+                "required": ["path"]
+            }
+        }
+    ])
+}
+
+fn call_tool(name: &str, args: &Value) -> Result<String> {
+    let access = match name {
+        "add_bookmark" => Access::Write,
+        _ => Access::Read,
+    };
+    let config = Config::load()?;
+    let mut db = db::load(access)?;
+    match name {
+        "list_bookmarks" => {
+            let filter = args["filter"].as_str().unwrap_or_default();
+            let tag = args["tag"].as_str();
+            let matching = db
+                .bookmarks
+                .iter()
+                .filter(|(name, _)| name.contains(filter))
+                .filter(|(_, b)| tag.is_none_or(|tag| b.has_tag(tag)));
+            Ok(resolve::expanded(&db.bookmarks, matching)
+                .iter()
+                .map(|(name, b)| match b.tags.is_empty() {
+                    true => format!("{name}: {}\n", b.path),
+                    false => format!("{name}: {} [{}]\n", b.path, b.tags.join(", ")),
+                })
+                .collect())
+        }
+        "get_bookmark" => {
+            let bookmark = args["name"].as_str().context("missing `name` argument")?;
+            let (name, subpath) = lookup(&db, &config, bookmark, &[], false)?;
+            let path = visit(&mut db, &config, &name, &subpath, true)?;
+            Ok(path.to_string_lossy().to_string())
+        }
+        "add_bookmark" => {
+            let path = args["path"].as_str().context("missing `path` argument")?;
+            let alias = args["alias"].as_str().map(String::from);
+            let overwrite = args["overwrite"].as_bool().unwrap_or(false);
+            let path = Some(PathBuf::from(path));
+            let meta = Metadata::default();
+            let (name, marked) = save_mark(&mut db, &config, path, alias, meta, || overwrite)?;
+            if let Marked::Kept = marked {
+                bail!("{name} already exists in bookmarks, pass `overwrite` to replace it");
+            }
+            Ok(format!("bookmarked {name} -> {}", db.bookmarks[&name].path))
+        }
+        _ => bail!("unknown tool: {name}"),
+    }
+}
+
+fn success(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
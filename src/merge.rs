@@ -0,0 +1,264 @@
+//! Combining another bookmarks file into the current database, for keeping several
+//! machines in sync.
+
+use crate::{
+    backup,
+    config::Config,
+    db::{self, Bookmarks, Database},
+    import::{drop_taken_aliases, free_name, OnConflict},
+    is_clip, new_table, print_table,
+    storage::Backend,
+    trash,
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::{io, path::Path};
+
+enum Outcome {
+    Added,
+    Updated,
+    Unchanged,
+    Skipped,
+    Overwritten,
+    Renamed(String),
+}
+
+/// Name, incoming path and what became of it
+type Merged = (String, String, Outcome);
+
+impl Outcome {
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Added => "added".green().to_string(),
+            Outcome::Updated => "updated".cyan().to_string(),
+            Outcome::Unchanged => "unchanged".dimmed().to_string(),
+            Outcome::Skipped => "skipped".yellow().to_string(),
+            Outcome::Overwritten => "overwritten".red().to_string(),
+            Outcome::Renamed(to) => format!("{} {to}", "renamed to".cyan()),
+        }
+    }
+}
+
+/// Reads any bookmarks file markd can store to, in full
+pub fn read(path: &Path) -> Result<Bookmarks> {
+    if !path.is_file() {
+        bail!("{} is not a file", path.display());
+    }
+    Backend::detect(path).open(path)?.load()
+}
+
+/// Unions `theirs` into the database. Entries for the same directory have their metadata
+/// combined, names pointing elsewhere are resolved with `policy` or by asking when
+/// `interactive` is set.
+pub fn merge(
+    db: &mut Database,
+    config: &Config,
+    theirs: Bookmarks,
+    policy: OnConflict,
+    interactive: bool,
+) -> Result<()> {
+    let (results, overwritten) = combine(db, theirs, policy, interactive)?;
+    let changed = results
+        .iter()
+        .filter(|(_, _, outcome)| !matches!(outcome, Outcome::Unchanged | Outcome::Skipped));
+    if changed.count() > 0 {
+        backup::auto(db, config)?;
+        if !overwritten.is_empty() {
+            trash::put(db, config.trash_days, overwritten)?;
+        }
+        db::save(db)?;
+    }
+    summary(&results);
+    Ok(())
+}
+
+/// Applies `theirs` to the database's entries without saving. Returns what happened
+/// to each name, along with the entries that got overwritten.
+fn combine(
+    db: &mut Database,
+    theirs: Bookmarks,
+    policy: OnConflict,
+    interactive: bool,
+) -> Result<(Vec<Merged>, Bookmarks)> {
+    let names: Vec<_> = theirs.keys().cloned().collect();
+    let mut results = vec![];
+    let mut overwritten = Bookmarks::new();
+    for (name, mut incoming) in theirs.into_iter().filter(|(name, _)| !is_clip(name)) {
+        // Archived entries and other bookmarks' aliases aren't entries to merge into
+        if db.archived.contains_key(&name) || db.alias_of(&name).is_some() {
+            results.push((name, incoming.path, Outcome::Skipped));
+            continue;
+        }
+        let (same_dir, policy) = match db.bookmarks.get(&name) {
+            None => (false, policy),
+            Some(ours) if ours.path == incoming.path => (true, policy),
+            Some(ours) if interactive => (false, ask(&name, &ours.path, &incoming.path)?),
+            Some(_) => (false, policy),
+        };
+        let exists = db.bookmarks.contains_key(&name);
+        // Entries merged into ours or replacing it may keep its aliases
+        let owner = match policy {
+            _ if same_dir => Some(name.as_str()),
+            OnConflict::Overwrite if exists => Some(name.as_str()),
+            _ => None,
+        };
+        if !exists || same_dir || !matches!(policy, OnConflict::Skip) {
+            drop_taken_aliases(db, &name, &mut incoming, owner, |alias| {
+                names.iter().any(|n| n == alias)
+            });
+        }
+        let outcome = match db.bookmarks.get_mut(&name) {
+            None => {
+                db.bookmarks.insert(name.clone(), incoming.clone());
+                Outcome::Added
+            }
+            Some(ours) if same_dir => match ours.absorb(&incoming) {
+                true => Outcome::Updated,
+                false => Outcome::Unchanged,
+            },
+            Some(ours) => match policy {
+                OnConflict::Skip => Outcome::Skipped,
+                OnConflict::Overwrite => {
+                    let old = std::mem::replace(ours, incoming.clone());
+                    overwritten.insert(name.clone(), old);
+                    Outcome::Overwritten
+                }
+                OnConflict::Rename => {
+                    let to = free_name(db, &name, |candidate| names.iter().any(|n| n == candidate));
+                    db.bookmarks.insert(to.clone(), incoming.clone());
+                    Outcome::Renamed(to)
+                }
+            },
+        };
+        results.push((name, incoming.path, outcome));
+    }
+    Ok((results, overwritten))
+}
+
+fn summary(results: &[Merged]) {
+    let shown: Vec<_> = results
+        .iter()
+        .filter(|(_, _, outcome)| !matches!(outcome, Outcome::Unchanged))
+        .collect();
+    let unchanged = results.len() - shown.len();
+    if shown.is_empty() {
+        println!(
+            "{} nothing to merge, all {unchanged} bookmarks are already up to date",
+            "Info:".yellow().bold()
+        );
+        return;
+    }
+    let mut table = new_table(["Name", "Path", "Result"]);
+    for (name, path, outcome) in shown {
+        table.push_record([name.as_str(), path, &outcome.describe()]);
+    }
+    print_table(table);
+    if unchanged > 0 {
+        println!("{unchanged} bookmarks were already up to date");
+    }
+}
+
+fn ask(name: &str, ours: &str, theirs: &str) -> Result<OnConflict> {
+    println!(
+        "{} {} points to {} here and to {} in the other file",
+        "Info:".yellow().bold(),
+        name.magenta(),
+        ours.red(),
+        theirs.green()
+    );
+    loop {
+        println!("Keep ours (k), take theirs (t) or add theirs under a new name (r)?");
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .context("failed to read from standard input")?;
+        match answer.trim() {
+            "k" | "keep" => return Ok(OnConflict::Skip),
+            "t" | "theirs" => return Ok(OnConflict::Overwrite),
+            "r" | "rename" => return Ok(OnConflict::Rename),
+            "" if answer.is_empty() => bail!("merge cancelled"),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{fixtures::database, Bookmark};
+
+    fn theirs() -> Bookmarks {
+        let api = Bookmark {
+            tags: vec!["work".into()],
+            hits: 3,
+            ..Bookmark::new("/src/api".into())
+        };
+        Bookmarks::from([
+            ("api".to_string(), api),
+            ("web".to_string(), Bookmark::new("/elsewhere/web".into())),
+            ("old".to_string(), Bookmark::new("/elsewhere/old".into())),
+            ("docs".to_string(), Bookmark::new("/src/docs".into())),
+        ])
+    }
+
+    fn outcome<'a>(results: &'a [Merged], name: &str) -> &'a Outcome {
+        &results.iter().find(|(n, _, _)| n == name).unwrap().2
+    }
+
+    #[test]
+    fn same_directory_is_combined() {
+        let mut db = database();
+        let (results, _) = combine(&mut db, theirs(), OnConflict::Skip, false).unwrap();
+        assert!(matches!(outcome(&results, "api"), Outcome::Updated));
+        assert!(matches!(outcome(&results, "docs"), Outcome::Added));
+        assert_eq!(db.bookmarks["api"].tags, ["work"]);
+        assert_eq!(db.bookmarks["api"].hits, 3);
+    }
+
+    #[test]
+    fn skip_keeps_ours() {
+        let mut db = database();
+        let (results, overwritten) = combine(&mut db, theirs(), OnConflict::Skip, false).unwrap();
+        assert!(matches!(outcome(&results, "web"), Outcome::Skipped));
+        assert!(matches!(outcome(&results, "old"), Outcome::Skipped));
+        assert_eq!(db.bookmarks["web"].path, "/src/web");
+        assert!(overwritten.is_empty());
+    }
+
+    #[test]
+    fn overwrite_hands_back_the_replaced_entry() {
+        let mut db = database();
+        let (results, overwritten) =
+            combine(&mut db, theirs(), OnConflict::Overwrite, false).unwrap();
+        assert!(matches!(outcome(&results, "web"), Outcome::Overwritten));
+        assert_eq!(db.bookmarks["web"].path, "/elsewhere/web");
+        assert_eq!(overwritten["web"].path, "/src/web");
+        // Archived entries are never replaced
+        assert!(matches!(outcome(&results, "old"), Outcome::Skipped));
+        assert_eq!(db.archived["old"].path, "/src/old");
+    }
+
+    #[test]
+    fn rename_adds_under_a_free_name() {
+        let mut db = database();
+        let (results, _) = combine(&mut db, theirs(), OnConflict::Rename, false).unwrap();
+        assert!(matches!(outcome(&results, "web"), Outcome::Renamed(to) if to == "web-2"));
+        assert_eq!(db.bookmarks["web"].path, "/src/web");
+        assert_eq!(db.bookmarks["web-2"].path, "/elsewhere/web");
+    }
+
+    #[test]
+    fn aliases_that_are_taken_are_dropped() {
+        let mut db = database();
+        let mut theirs = theirs();
+        theirs.get_mut("docs").unwrap().aliases = vec!["web".into(), "backend".into(), "d".into()];
+        theirs.get_mut("api").unwrap().aliases = vec!["backend".into(), "old".into()];
+        // Their name for one of our aliases is left alone
+        theirs.insert("backend".into(), Bookmark::new("/elsewhere/backend".into()));
+        let (results, _) = combine(&mut db, theirs, OnConflict::Overwrite, false).unwrap();
+        assert_eq!(db.bookmarks["docs"].aliases, ["d"]);
+        assert_eq!(db.bookmarks["api"].aliases, ["backend"]);
+        assert!(matches!(outcome(&results, "backend"), Outcome::Skipped));
+        assert!(!db.bookmarks.contains_key("backend"));
+    }
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use console::Term;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Prints `text`, through `$PAGER` (or `less -R`) when it's taller than the terminal.
+/// Falls back to printing when stdout isn't a terminal or the pager can't be started.
+pub fn page(text: &str, enabled: bool) -> Result<()> {
+    let term = Term::stdout();
+    let fits = term
+        .size_checked()
+        .is_none_or(|(rows, _)| text.lines().count() < rows as usize);
+    if !enabled || !term.is_term() || fits {
+        print!("{text}");
+        return Ok(());
+    }
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -R".into());
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().expect("pager isn't empty");
+    let Ok(mut child) = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        print!("{text}");
+        return Ok(());
+    };
+    // Quitting the pager early closes its input, that's not an error
+    let _ = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(text.as_bytes());
+    child.wait().context("failed to wait for the pager")?;
+    Ok(())
+}
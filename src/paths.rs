@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+/// Accepts `file://` URIs and percent-encoded paths, as produced by dragging folders
+/// from a file manager into the terminal. Anything else is returned untouched.
+pub fn decode_path_arg(raw: PathBuf) -> PathBuf {
+    let Some(s) = raw.to_str() else {
+        return raw;
+    };
+    if let Some(rest) = s.strip_prefix("file://") {
+        // Skip the optional authority, e.g. `file://localhost/home/user`
+        let path = rest.find('/').map_or("", |i| &rest[i..]);
+        let path = strip_drive_slash(path);
+        return percent_decode(path).map_or_else(|| PathBuf::from(path), PathBuf::from);
+    }
+    if s.contains('%') && !raw.exists() {
+        if let Some(decoded) = percent_decode(s).map(PathBuf::from) {
+            if decoded.exists() {
+                return decoded;
+            }
+        }
+    }
+    raw
+}
+
+/// `file:///C:/Users` carries the drive letter after a leading slash on Windows
+fn strip_drive_slash(path: &str) -> &str {
+    let bytes = path.as_bytes();
+    match bytes {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => &path[1..],
+        _ => path,
+    }
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// `path` with its leading `from` directory swapped for `to`, `None` unless `from`
+/// covers whole components, so `/home/a` moves `/home/a/src` but not `/home/abc`
+pub fn replace_prefix(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(from.trim_end_matches(['/', '\\']))?;
+    let boundary = rest.is_empty() || rest.starts_with(['/', '\\']);
+    boundary.then(|| format!("{}{rest}", to.trim_end_matches(['/', '\\'])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_prefix_matches_whole_components() {
+        assert_eq!(
+            replace_prefix("/home/me/src/api", "/home/me", "/Users/me").as_deref(),
+            Some("/Users/me/src/api")
+        );
+        assert_eq!(
+            replace_prefix("/home/me", "/home/me", "/Users/me").as_deref(),
+            Some("/Users/me")
+        );
+        assert_eq!(
+            replace_prefix("/home/meg/src", "/home/me", "/Users/me"),
+            None
+        );
+        assert_eq!(
+            replace_prefix("/srv/home/me", "/home/me", "/Users/me"),
+            None
+        );
+    }
+
+    #[test]
+    fn replace_prefix_ignores_trailing_separators() {
+        assert_eq!(
+            replace_prefix("/home/me/src", "/home/me/", "/Users/me/").as_deref(),
+            Some("/Users/me/src")
+        );
+        assert_eq!(
+            replace_prefix(r"C:\Users\me\src", r"C:\Users\me\", "/home/me").as_deref(),
+            Some(r"/home/me\src")
+        );
+    }
+
+    #[test]
+    fn replace_prefix_takes_replacement_literally() {
+        assert_eq!(
+            replace_prefix("/old/src", "/old", "/new/$1").as_deref(),
+            Some("/new/$1/src")
+        );
+    }
+}
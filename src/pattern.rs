@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// Compiles a name pattern, a shell style glob (`*`, `?`) matching whole names unless
+/// `regex` is set, in which case it's a regular expression matching anywhere in the name
+pub fn compile(pattern: &str, regex: bool) -> Result<Regex> {
+    let source = match regex {
+        true => pattern.to_string(),
+        false => {
+            let mut source = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            source.push('$');
+            source
+        }
+    };
+    Regex::new(&source).with_context(|| format!("invalid pattern {pattern:?}"))
+}
@@ -0,0 +1,53 @@
+use crate::{db::Bookmarks, list};
+use anyhow::{bail, Context, Result};
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+/// Lets the user choose a bookmark with fzf, most used first. Returns `None` when
+/// the picker was cancelled or nothing matched.
+pub fn pick(bookmarks: &Bookmarks, query: Option<&str>) -> Result<Option<String>> {
+    let now = crate::db::now();
+    let mut entries: Vec<_> = bookmarks.iter().collect();
+    entries.sort_by(|(_, a), (_, b)| b.frecency(now).total_cmp(&a.frecency(now)));
+
+    let mut fzf = Command::new("fzf");
+    fzf.args([
+        "--delimiter=\t",
+        "--tiebreak=index",
+        "--height=40%",
+        "--reverse",
+    ]);
+    if cfg!(unix) {
+        fzf.args(["--preview", "ls {2}"]);
+    }
+    if let Some(query) = query {
+        fzf.args(["--query", query]);
+    }
+    let mut child = match fzf.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            bail!("fzf is not installed, get it from https://github.com/junegunn/fzf")
+        }
+        Err(err) => return Err(err).context("failed to run fzf"),
+    };
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    match list::write_picker(&mut stdin, bookmarks, entries.into_iter()) {
+        // fzf stops reading once a selection is made
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+            return Err(err).context("failed to write to fzf")
+        }
+        _ => drop(stdin),
+    }
+    let output = child.wait_with_output().context("failed to run fzf")?;
+    match output.status.code() {
+        Some(0) => {}
+        // No match and cancelled
+        Some(1 | 130) => return Ok(None),
+        _ => bail!("fzf exited with {}", output.status),
+    }
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let name = selection.split('\t').next().unwrap_or_default().trim_end();
+    Ok(Some(name.to_string()))
+}
@@ -0,0 +1,120 @@
+use crate::{
+    confirm,
+    db::{self, Access, Database},
+    new_table, print_table,
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::{io::ErrorKind, path::PathBuf};
+
+/// Profile backed by the main bookmarks file
+pub const DEFAULT: &str = "default";
+
+/// Profile names end up in file names
+pub fn parse_name(name: &str) -> Result<String, String> {
+    let name = name.trim().to_lowercase();
+    let valid = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+    match valid && !name.is_empty() {
+        true => Ok(name),
+        false => Err("profile names can only contain letters, digits, '-' and '_'".into()),
+    }
+}
+
+pub fn path(name: &str) -> PathBuf {
+    match name {
+        DEFAULT => db::default_path(),
+        _ => profiles_dir().join(format!("{name}.toml")),
+    }
+}
+
+fn profiles_dir() -> PathBuf {
+    db::data_home().join("profiles")
+}
+
+/// The default profile followed by every profile with a file, in name order
+fn names() -> Result<Vec<String>> {
+    let dir = profiles_dir();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![DEFAULT.into()]),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let mut names = vec![];
+    for entry in entries {
+        let path = entry.context("failed to read profiles directory")?.path();
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            if let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    names.retain(|name| name != DEFAULT);
+    names.insert(0, DEFAULT.into());
+    Ok(names)
+}
+
+pub fn list(active: &str) -> Result<()> {
+    println!("{}", "Profiles:".green().bold());
+    let mut table = new_table(["Profile", "Bookmarks", "Path", ""]);
+    for name in names()? {
+        let path = path(&name);
+        let count = match path.exists() {
+            true => db::load_from(&path, Access::Read)?.bookmarks.len(),
+            false => 0,
+        };
+        let marker = if name == active { "active" } else { "" };
+        table.push_record([
+            name,
+            count.to_string(),
+            path.display().to_string(),
+            marker.into(),
+        ]);
+    }
+    print_table(table);
+    Ok(())
+}
+
+/// Copies bookmarks from the active profile into another one
+pub fn copy(db: &Database, bookmarks: &[String], active: &str, to: &str) -> Result<()> {
+    if active == to {
+        bail!("can't copy bookmarks to the profile they're in");
+    }
+    let target_path = path(to);
+    if let Some(parent) = target_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut target = db::load_from(&target_path, Access::Write)?;
+    let mut copied = vec![];
+    for name in bookmarks {
+        let entry = db
+            .bookmarks
+            .get(name)
+            .with_context(|| format!("{} is not in bookmarks", name))?;
+        if target.bookmarks.contains_key(name)
+            && !confirm(&format!(
+                "{name} already exists in the {to} profile, would you like to overwrite it?"
+            ))
+        {
+            continue;
+        }
+        target.bookmarks.insert(name.clone(), entry.clone());
+        copied.push(name.as_str());
+    }
+    if copied.is_empty() {
+        println!("{} nothing copied", "Info:".yellow().bold());
+        return Ok(());
+    }
+    // Saving through the loaded storage also clears the journal that was replayed into it
+    db::save(&target)?;
+    println!(
+        "{} {} copied to the {} profile",
+        "Success:".green().bold(),
+        copied.join(", ").magenta(),
+        to.magenta()
+    );
+    Ok(())
+}
@@ -0,0 +1,155 @@
+//! Per-project bookmarks, read from a `.markd.toml` (or `.markd`) file in the
+//! current directory or any of its parents, much like direnv scopes `.envrc`.
+//! Like `.envrc` files, a project file is only used once it's been allowed, so a
+//! cloned repository can't point your bookmarks somewhere else behind your back.
+
+use crate::{
+    db::{self, Access, Database, Source},
+    storage,
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+const FILE_NAMES: [&str; 2] = [".markd.toml", ".markd"];
+
+/// Project files that were allowed, by path, with the SHA-256 of the contents they
+/// were allowed with. Any change to a file has to be allowed again.
+#[derive(Serialize, Deserialize, Default)]
+struct Allowed {
+    #[serde(default)]
+    allowed: BTreeMap<String, String>,
+}
+
+impl Allowed {
+    /// `markd/allowed.toml` in the state directory
+    fn path() -> PathBuf {
+        db::state_home().join("allowed.toml")
+    }
+
+    fn load() -> Result<Self> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            raw => {
+                let raw = raw.with_context(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let toml = toml::to_string(self).context("failed to serialize allowed project files")?;
+        storage::write_atomic(&path, toml.as_bytes())
+    }
+}
+
+/// Canonical path used as the key, with the hash of the file's current contents
+fn fingerprint(file: &Path) -> Result<(String, String)> {
+    let key = file
+        .canonicalize()
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let raw = std::fs::read(&key).with_context(|| format!("failed to read {}", key.display()))?;
+    let hash = Sha256::digest(raw)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    Ok((key.to_string_lossy().to_string(), hash))
+}
+
+fn is_allowed(file: &Path) -> Result<bool> {
+    let (key, hash) = fingerprint(file)?;
+    Ok(Allowed::load()?.allowed.get(&key) == Some(&hash))
+}
+
+/// Closest project file, searching upwards from the current directory
+pub fn find() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .flat_map(|dir| FILE_NAMES.map(|name| dir.join(name)))
+        .find(|file| file.is_file())
+}
+
+fn find_or(file: Option<PathBuf>) -> Result<PathBuf> {
+    match file.or_else(find) {
+        Some(file) => Ok(file),
+        None => bail!("no .markd.toml or .markd file in this directory or its parents"),
+    }
+}
+
+/// Trusts the project file as it is now, the closest one unless `file` is given
+pub fn allow(file: Option<PathBuf>, quiet: bool) -> Result<()> {
+    let file = find_or(file)?;
+    let (key, hash) = fingerprint(&file)?;
+    let mut allowed = Allowed::load()?;
+    allowed.allowed.insert(key.clone(), hash);
+    allowed.save()?;
+    if !quiet {
+        println!("{} {key} allowed", "Success:".green().bold());
+    }
+    Ok(())
+}
+
+pub fn deny(file: Option<PathBuf>) -> Result<()> {
+    let file = find_or(file)?;
+    let (key, _) = fingerprint(&file)?;
+    let mut allowed = Allowed::load()?;
+    if allowed.allowed.remove(&key).is_none() {
+        println!("{} {key} wasn't allowed", "Info:".yellow().bold());
+        return Ok(());
+    }
+    allowed.save()?;
+    println!("{} {key} is no longer allowed", "Success:".green().bold());
+    Ok(())
+}
+
+/// Loads the bookmarks with the project ones merged in. Project bookmarks shadow
+/// the user's and are read-only unless `writable`, in which case the project file
+/// is the one that gets modified and the user's bookmarks are only looked up.
+/// Project files that weren't allowed are left out with a notice.
+pub fn load(writable: bool, access: Access) -> Result<Database> {
+    let file = find();
+    let allowed = match &file {
+        Some(file) => is_allowed(file)?,
+        None => false,
+    };
+    match (file, writable) {
+        (Some(file), _) if !allowed => {
+            let hint = format!("run `markd project allow` to use {}", file.display());
+            if writable {
+                bail!("{} isn't allowed yet, {hint}", file.display());
+            }
+            eprintln!(
+                "{} ignoring project bookmarks that weren't allowed, {hint}",
+                "Info:".yellow().bold()
+            );
+            db::load(access)
+        }
+        (Some(file), true) => {
+            let mut db = db::load_project(&file, access)?;
+            db.merge(Source::User, db::load(Access::Read)?.bookmarks, false);
+            Ok(db)
+        }
+        (Some(file), false) => {
+            let project = db::load_project(&file, Access::Read)
+                .with_context(|| format!("failed to load project file {}", file.display()))?
+                .bookmarks;
+            let mut db = db::load(access)?;
+            db.merge(Source::Project, project, true);
+            Ok(db)
+        }
+        (None, true) => bail!("no .markd.toml or .markd file in this directory or its parents"),
+        (None, false) => db::load(access),
+    }
+}
@@ -0,0 +1,159 @@
+//! Finding where bookmarked directories went after they were moved or a parent
+//! directory was renamed.
+
+use crate::{
+    confirm,
+    db::{self, Database},
+    new_table, print_table,
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use dialoguer::Select;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+/// Directories looked at per search, so a search near the root of a large tree ends quickly
+const VISIT_LIMIT: usize = 20_000;
+
+/// Looks for a directory with the same name near each dead bookmark and offers to
+/// point the bookmark there. Checks every dead bookmark unless one is given.
+pub fn fix(db: &mut Database, bookmark: Option<&str>, dry_run: bool) -> Result<()> {
+    let dead: Vec<_> = match bookmark {
+        Some(name) => {
+            let entry = db
+                .bookmarks
+                .get(name)
+                .with_context(|| format!("{name} is not in bookmarks"))?;
+            if Path::new(&entry.path).is_dir() {
+                bail!("{name} still points to an existing directory");
+            }
+            vec![name.to_string()]
+        }
+        None => db
+            .bookmarks
+            .iter()
+            .filter(|(name, b)| {
+                !b.path.starts_with('@')
+                    && !Path::new(&b.path).is_dir()
+                    && db.source_of(name) == db.source
+            })
+            .map(|(name, _)| name.clone())
+            .collect(),
+    };
+    if dead.is_empty() {
+        println!(
+            "{} no bookmarks point to missing directories",
+            "Info:".yellow().bold()
+        );
+        return Ok(());
+    }
+
+    let mut moved = vec![];
+    let mut table = new_table(["Name", "Old path", "Found"]);
+    for name in dead {
+        let old = db.bookmarks[&name].path.clone();
+        let candidates = candidates(Path::new(&old));
+        let found = match candidates.as_slice() {
+            [] => "-".dimmed().to_string(),
+            paths => paths
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        table.push_record([name.clone(), old.clone(), found]);
+        if dry_run || candidates.is_empty() {
+            continue;
+        }
+        if let Some(new) = choose(&name, &old, &candidates)? {
+            moved.push((name, old, new));
+        }
+    }
+    if dry_run {
+        print_table(table);
+        return Ok(());
+    }
+    if moved.is_empty() {
+        println!("{} no bookmarks were updated", "Info:".yellow().bold());
+        return Ok(());
+    }
+    for (name, _, new) in &moved {
+        db.bookmarks.get_mut(name).expect("dead names exist").path = new.clone();
+    }
+    db::save(db)?;
+    for (name, old, new) in &moved {
+        println!("{} {} moved", "Success:".green().bold(), name.magenta());
+        crate::print_path_change(old, new);
+    }
+    Ok(())
+}
+
+fn choose(name: &str, old: &str, candidates: &[PathBuf]) -> Result<Option<String>> {
+    if let [only] = candidates {
+        let only = only.to_string_lossy().to_string();
+        let question = format!("{name} pointed to {old}, update it to {only}?");
+        return Ok(confirm(&question).then_some(only));
+    }
+    let items: Vec<_> = candidates.iter().map(|p| p.display().to_string()).collect();
+    let picked = Select::new()
+        .with_prompt(format!(
+            "{name} pointed to {old}, pick where it went (esc to skip)"
+        ))
+        .items(&items)
+        .interact_opt()
+        .context("failed to show the selection, pass a single bookmark or use a terminal")?;
+    Ok(picked.map(|i| items[i].clone()))
+}
+
+/// Directories named like the missing one below its closest existing ancestor, and a
+/// little less deep below the ancestor's parent
+fn candidates(missing: &Path) -> Vec<PathBuf> {
+    let Some(wanted) = missing.file_name() else {
+        return vec![];
+    };
+    let Some(ancestor) = missing.ancestors().skip(1).find(|a| a.is_dir()) else {
+        return vec![];
+    };
+    let mut roots = vec![(ancestor, 4)];
+    roots.extend(ancestor.parent().map(|parent| (parent, 2)));
+    let mut found = vec![];
+    for (root, depth) in roots {
+        for dir in search(root, wanted, depth) {
+            if !found.contains(&dir) {
+                found.push(dir);
+            }
+        }
+    }
+    found
+}
+
+fn search(root: &Path, wanted: &std::ffi::OsStr, max_depth: usize) -> Vec<PathBuf> {
+    let mut found = vec![];
+    let mut queue = VecDeque::from([(root.to_path_buf(), 0)]);
+    let mut visited = 0;
+    while let Some((dir, depth)) = queue.pop_front() {
+        visited += 1;
+        if visited > VISIT_LIMIT {
+            break;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Symlinks are skipped to stay out of loops
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_name() == wanted {
+                found.push(path.clone());
+            }
+            if depth + 1 < max_depth {
+                queue.push_back((path, depth + 1));
+            }
+        }
+    }
+    found
+}
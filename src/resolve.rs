@@ -0,0 +1,228 @@
+use crate::db::{Bookmark, Bookmarks};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::{borrow::Cow, path::Path};
+
+/// Resolves user input to a bookmark name. Exact names always win, then names
+/// starting with the query, then names containing it. A unique candidate is picked
+/// right away, several are ranked by frecency and only resolve if one comes out on top.
+/// Pinned candidates are preferred over the rest.
+pub fn resolve<'a>(bookmarks: &'a Bookmarks, query: &'a str) -> Result<&'a str> {
+    resolve_with(bookmarks, query, |_| 0.0)
+}
+
+/// Like `resolve`, with `tiebreak` ranking the candidates frecency can't tell apart
+pub fn resolve_with<'a>(
+    bookmarks: &'a Bookmarks,
+    query: &'a str,
+    tiebreak: impl Fn(&Bookmark) -> f64,
+) -> Result<&'a str> {
+    if let Some((name, _)) = bookmarks.get_key_value(query) {
+        return Ok(name);
+    }
+    let mut candidates = matching(bookmarks, |name| name.starts_with(query));
+    if candidates.is_empty() {
+        candidates = matching(bookmarks, |name| name.contains(query));
+    }
+    if candidates.iter().any(|(_, b)| b.pinned) {
+        candidates.retain(|(_, b)| b.pinned);
+    }
+    match candidates.as_slice() {
+        [] => bail!("{query} is not in bookmarks"),
+        [(name, _)] => return Ok(name),
+        _ => {}
+    }
+    let now = crate::db::now();
+    let mut ranked: Vec<_> = candidates
+        .iter()
+        .map(|(name, b)| (*name, b.frecency(now), tiebreak(b)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(b.2.total_cmp(&a.2)));
+    match ranked.as_slice() {
+        [(name, f1, t1), (_, f2, t2), ..] if (f1, t1) > (f2, t2) => Ok(name),
+        _ => {
+            let names: Vec<_> = candidates.iter().map(|(name, _)| *name).collect();
+            bail!(
+                "{query} is ambiguous, it could be any of: {}",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+fn matching(bookmarks: &Bookmarks, pred: impl Fn(&str) -> bool) -> Vec<(&str, &Bookmark)> {
+    bookmarks
+        .iter()
+        .filter(|(name, _)| pred(name))
+        .map(|(name, b)| (name.as_str(), b))
+        .collect()
+}
+
+/// Follows `@name/subdir` references to other bookmarks, so a bookmark can be defined
+/// relative to another one and moves along with it. References may chain, cycles fail.
+pub fn expand(bookmarks: &Bookmarks, path: &str) -> Result<String> {
+    let mut path = path.to_string();
+    let mut seen: Vec<String> = vec![];
+    while let Some(reference) = path.strip_prefix('@') {
+        let (name, rest) = reference.split_once('/').unwrap_or((reference, ""));
+        if seen.iter().any(|s| s == name) {
+            bail!(
+                "bookmark references form a cycle: {} -> {name}",
+                seen.join(" -> ")
+            );
+        }
+        let target = bookmarks
+            .get(name)
+            .or_else(|| {
+                bookmarks
+                    .values()
+                    .find(|b| b.aliases.iter().any(|a| a == name))
+            })
+            .with_context(|| format!("{name} is not in bookmarks"))?;
+        let expanded = match rest.is_empty() {
+            true => target.path.clone(),
+            false => Path::new(&target.path)
+                .join(rest)
+                .to_string_lossy()
+                .to_string(),
+        };
+        seen.push(name.to_string());
+        path = expanded;
+    }
+    Ok(path)
+}
+
+/// `entries` with their references followed, for output that has to name real directories
+/// like aliases and session layouts. Entries whose references can't be followed are
+/// reported and left out.
+pub fn expanded<'a>(
+    bookmarks: &Bookmarks,
+    entries: impl IntoIterator<Item = (&'a String, &'a Bookmark)>,
+) -> Bookmarks {
+    entries
+        .into_iter()
+        .filter_map(|(name, b)| match expand(bookmarks, &b.path) {
+            Ok(path) => Some((name.clone(), Bookmark { path, ..b.clone() })),
+            Err(err) => {
+                eprintln!("{} skipped {name}, {err:#}", "Info:".yellow().bold());
+                None
+            }
+        })
+        .collect()
+}
+
+/// Directory a bookmark points to, references that can't be followed are left as they are
+pub fn target<'a>(bookmarks: &Bookmarks, bookmark: &'a Bookmark) -> Cow<'a, str> {
+    match bookmark.path.starts_with('@') {
+        true => expand(bookmarks, &bookmark.path).map_or(Cow::Borrowed(&bookmark.path), Cow::Owned),
+        false => Cow::Borrowed(&bookmark.path),
+    }
+}
+
+/// Name a `@name/subdir` reference points to, `None` for plain paths
+pub fn referenced(path: &str) -> Option<&str> {
+    let reference = path.strip_prefix('@')?;
+    Some(
+        reference
+            .split_once('/')
+            .map_or(reference, |(name, _)| name),
+    )
+}
+
+/// Names of the entries referring to any of `names`
+pub fn referencing<'a>(bookmarks: &'a Bookmarks, names: &[&str]) -> Vec<&'a str> {
+    bookmarks
+        .iter()
+        .filter(|(_, b)| referenced(&b.path).is_some_and(|r| names.contains(&r)))
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
+
+/// Points references to `old` at `new`, keeping their subdirectories. Returns how many
+/// entries were changed.
+pub fn retarget(bookmarks: &mut Bookmarks, old: &str, new: &str) -> usize {
+    let mut changed = 0;
+    for bookmark in bookmarks.values_mut() {
+        if referenced(&bookmark.path) == Some(old) {
+            bookmark.path = format!("@{new}{}", &bookmark.path[old.len() + 1..]);
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::fixtures::bookmarks;
+
+    #[test]
+    fn expand_follows_references() {
+        let db = bookmarks(&[
+            ("src", "/home/me/src"),
+            ("api", "@src/api"),
+            ("v2", "@api/v2"),
+        ]);
+        assert_eq!(expand(&db, "@v2").unwrap(), "/home/me/src/api/v2");
+        assert_eq!(expand(&db, "@api/tests").unwrap(), "/home/me/src/api/tests");
+        assert_eq!(expand(&db, "/plain").unwrap(), "/plain");
+    }
+
+    #[test]
+    fn expand_follows_aliases() {
+        let mut db = bookmarks(&[("src", "/home/me/src"), ("api", "@code/api")]);
+        db.get_mut("src").unwrap().aliases.push("code".into());
+        assert_eq!(expand(&db, "@api").unwrap(), "/home/me/src/api");
+    }
+
+    #[test]
+    fn expand_rejects_cycles() {
+        let db = bookmarks(&[("a", "@b"), ("b", "@c/x"), ("c", "@a")]);
+        let err = expand(&db, "@a").unwrap_err().to_string();
+        assert!(err.contains("cycle"), "{err}");
+        let db = bookmarks(&[("self", "@self")]);
+        assert!(expand(&db, "@self").is_err());
+    }
+
+    #[test]
+    fn expand_rejects_missing_targets() {
+        let db = bookmarks(&[("api", "@gone/api")]);
+        let err = expand(&db, "@api").unwrap_err().to_string();
+        assert!(err.contains("gone is not in bookmarks"), "{err}");
+    }
+
+    #[test]
+    fn retarget_follows_a_rename() {
+        let mut db = bookmarks(&[
+            ("backend", "/src/api"),
+            ("apisrc", "@api/src"),
+            ("root", "@api"),
+            ("apps", "@apidocs/src"),
+        ]);
+        assert_eq!(retarget(&mut db, "api", "backend"), 2);
+        assert_eq!(db["apisrc"].path, "@backend/src");
+        assert_eq!(db["root"].path, "@backend");
+        assert_eq!(db["apps"].path, "@apidocs/src");
+        assert_eq!(expand(&db, "@apisrc").unwrap(), "/src/api/src");
+    }
+
+    #[test]
+    fn referencing_finds_entries_that_would_dangle() {
+        let db = bookmarks(&[
+            ("api", "/src/api"),
+            ("apisrc", "@api/src"),
+            ("docs", "@backend/docs"),
+            ("web", "/src/web"),
+        ]);
+        assert_eq!(referencing(&db, &["api", "backend"]), ["apisrc", "docs"]);
+        assert!(referencing(&db, &["web"]).is_empty());
+    }
+
+    #[test]
+    fn expanded_leaves_out_broken_entries() {
+        let db = bookmarks(&[("src", "/src"), ("api", "@src/api"), ("loop", "@loop")]);
+        let out = expanded(&db, &db);
+        assert_eq!(out["api"].path, "/src/api");
+        assert!(!out.contains_key("loop"));
+    }
+}
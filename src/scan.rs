@@ -0,0 +1,98 @@
+//! Finding directories worth bookmarking, like the git checkouts under `~/src`.
+
+use crate::{
+    db::{Bookmark, Bookmarks},
+    jumpers::slugify,
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+/// Git repositories under `root` at most `max_depth` levels down, in the order they
+/// were found. Repositories aren't searched for nested ones, and hidden directories and
+/// symlinks are skipped.
+fn git_repos(root: &Path, max_depth: usize) -> Vec<PathBuf> {
+    let mut found = vec![];
+    let mut queue = VecDeque::from([(root.to_path_buf(), 0)]);
+    while let Some((dir, depth)) = queue.pop_front() {
+        // `.git` is a file in worktrees and submodules
+        if dir.join(".git").exists() {
+            found.push(dir);
+            continue;
+        }
+        if depth >= max_depth {
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut children: Vec<_> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .collect();
+        children.sort();
+        queue.extend(children.into_iter().map(|child| (child, depth + 1)));
+    }
+    found
+}
+
+/// Subdirectories exactly `depth` levels below `root`, sorted, leaving out hidden ones
+pub fn children(root: &Path, depth: usize) -> Vec<PathBuf> {
+    let mut level = vec![root.to_path_buf()];
+    for _ in 0..depth {
+        level = level
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .collect();
+    }
+    level.sort();
+    level
+}
+
+/// Bookmarks for the git repositories under `root`, named after their slugified
+/// directory names. A name found twice is prefixed with the parent directory's name,
+/// e.g. `forks-markd`, and repositories left without a free name are reported and skipped.
+pub fn git(root: &Path, max_depth: usize) -> Result<Bookmarks> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("failed to read {}", root.display()))?;
+    let repos = git_repos(&root, max_depth);
+    if repos.is_empty() {
+        bail!("no git repositories found under {}", root.display());
+    }
+    let slug = |dir: &Path| dir.file_name().map(|n| slugify(&n.to_string_lossy()));
+    let mut bookmarks = Bookmarks::new();
+    for repo in repos {
+        let Some(name) = slug(&repo).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        let name = match bookmarks.contains_key(&name) {
+            false => name,
+            true => match repo.parent().and_then(slug).filter(|p| !p.is_empty()) {
+                Some(parent) => format!("{parent}-{name}"),
+                None => name,
+            },
+        };
+        if let Some(winner) = bookmarks.get(&name) {
+            println!(
+                "{} skipped {}, {} is already taken by {}",
+                "Info:".yellow().bold(),
+                repo.display(),
+                name.magenta(),
+                winner.path
+            );
+            continue;
+        }
+        bookmarks.insert(name, Bookmark::new(repo.to_string_lossy().to_string()));
+    }
+    Ok(bookmarks)
+}
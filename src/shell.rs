@@ -0,0 +1,612 @@
+use crate::{confirm, db::Bookmarks, resolve, Cli};
+use anyhow::{bail, Context, Result};
+use clap::{Command, CommandFactory, ValueEnum};
+use colored::Colorize;
+use dirs::home_dir;
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// `-i` picks the bookmark with fzf, staying put when the picker is cancelled
+const ZSH_BASH: &str = r#"{cmd}() {
+    if [ "$1" = -i ]; then
+        local dir
+        dir=$(markd pick $2) && [ -n "$dir" ] && cd "$dir";
+    else
+        cd $(markd g $1);
+    fi
+}"#;
+const FISH: &str = r"function {cmd}
+    if test (count $argv) -gt 0; and test $argv[1] = -i
+        set -l dir (markd pick $argv[2..]); and test -n $dir; and cd $dir
+    else
+        cd $(markd g $argv)
+    end
+end";
+const POWERSHELL: &str = r"function {cmd}([string]$Bookmark, [switch]$Interactive) {
+    if ($Interactive) {
+        $dir = markd pick $Bookmark
+        if ($dir) { cd $dir }
+    } else {
+        cd (markd g $Bookmark)
+    }
+}";
+// Nushell can only change directory from a `def --env` command, not a substitution
+const NUSHELL: &str = r#"def "nu-complete markd bookmarks" [] {
+    ^markd _complete | lines
+}
+
+def --env {cmd} [bookmark?: string@"nu-complete markd bookmarks", --interactive (-i)] {
+    let path = if $interactive {
+        if $bookmark == null { ^markd pick } else { ^markd pick $bookmark }
+    } else if $bookmark == null { ^markd g } else { ^markd g $bookmark }
+    if $path != "" { cd $path }
+}"#;
+const ELVISH: &str = r"fn {cmd} {|@args|
+    if (and (> (count $args) 0) (eq $args[0] -i)) {
+        var dir = (markd pick $@args[1..] | slurp)
+        if (!=s $dir '') { cd $dir }
+    } else {
+        cd (markd g $@args)
+    }
+}";
+// Callable aliases run in xonsh itself, so they can change its directory
+const XONSH: &str = r"def _markd_goto(args):
+    from xonsh.dirstack import cd
+    if args and args[0] == '-i':
+        path = $(markd pick @(args[1:]))
+    else:
+        path = $(markd g @(args))
+    if path:
+        cd([path])
+
+aliases['{cmd}'] = _markd_goto";
+
+// Ctrl-G picks a bookmark with fzf, jumping to it on an empty prompt or inserting its path
+const ZSH_WIDGET: &str = r#"_markd_pick() {
+    local line name
+    line=$(markd list --picker --sort frecency | fzf --height 40% --reverse --tiebreak=index)
+    if [[ -z $line ]]; then
+        zle reset-prompt
+        return
+    fi
+    name=${line%%$'	'*}
+    name=${name%"${name##*[![:space:]]}"}
+    if [[ -z $BUFFER ]]; then
+        BUFFER="{cmd} ${(q)name}"
+        zle accept-line
+    else
+        LBUFFER+=${(q)${line#*$'	'}}
+        zle reset-prompt
+    fi
+}
+zle -N _markd_pick
+bindkey '^G' _markd_pick"#;
+const FISH_WIDGET: &str = r#"function _markd_pick
+    set -l line (markd list --picker --sort frecency | fzf --height 40% --reverse --tiebreak=index)
+    if test -n "$line"
+        set -l fields (string split -m 1 	 -- $line)
+        if test -z (commandline)
+            commandline -r "{cmd} "(string escape -- (string trim -r -- $fields[1]))
+            commandline -f execute
+        else
+            commandline -i (string escape -- $fields[2])
+        end
+    end
+    commandline -f repaint
+end
+bind \cg _markd_pick"#;
+
+// `markd --prev` reads $OLDPWD, which bash exports by itself but zsh and fish don't
+const ZSH_OLDPWD: &str = "export OLDPWD";
+const FISH_OLDPWD: &str = r"function _markd_oldpwd --on-variable PWD
+    set -gx OLDPWD $dirprev[-1]
+end";
+
+// `--track` records every directory change with `markd track`. Bash has no chpwd
+// hook, so the prompt hook compares against the last directory it saw.
+const ZSH_TRACK: &str = r#"_markd_track() {
+    markd track "$PWD"
+}
+chpwd_functions+=(_markd_track)"#;
+const BASH_TRACK: &str = r#"_markd_track() {
+    if [ "$_markd_pwd" != "$PWD" ]; then
+        _markd_pwd=$PWD
+        markd track "$PWD"
+    fi
+}
+PROMPT_COMMAND="_markd_track${PROMPT_COMMAND:+;$PROMPT_COMMAND}""#;
+const FISH_TRACK: &str = r"function _markd_track --on-variable PWD
+    markd track $PWD
+end";
+
+/// Name of the generated jump function unless `--cmd` is given
+pub const DEFAULT_CMD: &str = "goto";
+
+const BEGIN_MARKER: &str = "# >>> markd shell integration >>>";
+const END_MARKER: &str = "# <<< markd shell integration <<<";
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Shell {
+    Fish,
+    Zsh,
+    Bash,
+    Powershell,
+    #[value(alias = "nu")]
+    Nushell,
+    Elvish,
+    Xonsh,
+}
+
+impl Shell {
+    /// The jump function, named `cmd`
+    fn snippet(self, cmd: &str) -> String {
+        let template = match self {
+            Shell::Fish => FISH,
+            Shell::Zsh | Shell::Bash => ZSH_BASH,
+            Shell::Powershell => POWERSHELL,
+            Shell::Nushell => NUSHELL,
+            Shell::Elvish => ELVISH,
+            Shell::Xonsh => XONSH,
+        };
+        template.replace("{cmd}", cmd)
+    }
+
+    fn generate_completions(self, buf: &mut Vec<u8>) -> Result<()> {
+        use clap_complete::{generate, Shell as Gen};
+        let cmd = &mut completion_command();
+        match self {
+            Shell::Fish => generate(Gen::Fish, cmd, "markd", buf),
+            Shell::Zsh => generate(Gen::Zsh, cmd, "markd", buf),
+            Shell::Bash => generate(Gen::Bash, cmd, "markd", buf),
+            Shell::Powershell => generate(Gen::PowerShell, cmd, "markd", buf),
+            Shell::Nushell => generate(clap_complete_nushell::Nushell, cmd, "markd", buf),
+            Shell::Elvish => generate(Gen::Elvish, cmd, "markd", buf),
+            Shell::Xonsh => bail!("completions are not available for xonsh"),
+        }
+        Ok(())
+    }
+
+    /// Line added to the rc file by `--install`, loading `markd init` on startup
+    fn init_line(self, cmd: &str) -> String {
+        let name = self.to_possible_value().expect("no skipped variants");
+        let init = match cmd {
+            DEFAULT_CMD => format!("markd init {}", name.get_name()),
+            _ => format!("markd init {} --cmd {cmd}", name.get_name()),
+        };
+        match self {
+            Shell::Fish => format!("{init} | source"),
+            Shell::Zsh | Shell::Bash => format!(r#"eval "$({init})""#),
+            Shell::Powershell => format!("Invoke-Expression (& {init} | Out-String)"),
+            // Nushell can't evaluate generated code, `--install` saves it to this file instead
+            Shell::Nushell => r#"source ($nu.default-config-dir | path join "markd.nu")"#.into(),
+            Shell::Elvish => format!("eval ({init} | slurp)"),
+            Shell::Xonsh => format!("execx($({init}), 'exec', __xonsh__.ctx, filename='markd')"),
+        }
+    }
+
+    fn rc_file(self) -> Result<PathBuf> {
+        let home = home_dir().context("failed to get home directory")?;
+        let path = match self {
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => std::env::var_os("ZDOTDIR")
+                .map_or(home, PathBuf::from)
+                .join(".zshrc"),
+            Shell::Fish => std::env::var_os("XDG_CONFIG_HOME")
+                .map_or(home.join(".config"), PathBuf::from)
+                .join("fish")
+                .join("config.fish"),
+            Shell::Powershell if cfg!(windows) => dirs::document_dir()
+                .context("failed to get documents directory")?
+                .join("PowerShell")
+                .join("Microsoft.PowerShell_profile.ps1"),
+            Shell::Powershell => home
+                .join(".config")
+                .join("powershell")
+                .join("Microsoft.PowerShell_profile.ps1"),
+            Shell::Nushell => std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(dirs::config_dir)
+                .context("failed to get config directory")?
+                .join("nushell")
+                .join("config.nu"),
+            Shell::Elvish => std::env::var_os("XDG_CONFIG_HOME")
+                .map_or(home.join(".config"), PathBuf::from)
+                .join("elvish")
+                .join("rc.elv"),
+            Shell::Xonsh => home.join(".xonshrc"),
+        };
+        Ok(path)
+    }
+}
+
+pub fn print(shell: Shell, cmd: &str) {
+    println!("{}", shell.snippet(cmd))
+}
+
+/// Function names end up unquoted in every shell's syntax
+pub fn parse_cmd(cmd: &str) -> Result<String, String> {
+    let valid = cmd
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'));
+    match valid && !cmd.is_empty() && !cmd.starts_with('-') {
+        true => Ok(cmd.to_string()),
+        false => Err("names can only contain letters, digits, '-' and '_'".into()),
+    }
+}
+
+/// Subcommands whose first argument is a bookmark name, completed by calling `markd _complete`
+const BOOKMARK_COMMANDS: &[&str] = &["get", "remove", "note", "rename", "copy", "swap"];
+const BOOKMARK_NU: &str = "    bookmark: string@\"nu-complete markd bookmarks\"\n";
+const BOOKMARK_NU_OPTIONAL: &str = "    bookmark?: string@\"nu-complete markd bookmarks\"\n";
+
+pub fn completions(shell: Shell) -> Result<()> {
+    print!("{}", completion_script(shell)?);
+    Ok(())
+}
+
+/// Completion script covering markd's subcommands and flags, extended to
+/// complete bookmark names through the hidden `_complete` command
+fn completion_script(shell: Shell) -> Result<String> {
+    let mut script = vec![];
+    shell.generate_completions(&mut script)?;
+    let script = String::from_utf8(script).context("generated completions are not valid UTF-8")?;
+    complete_bookmarks(shell, script)
+}
+
+/// The generators don't skip hidden subcommands, so they are left out by hand
+fn completion_command() -> Command {
+    let cli = Cli::command();
+    let visible = cli.get_subcommands().filter(|sub| !sub.is_hide_set());
+    Command::new("markd")
+        .version(env!("CARGO_PKG_VERSION"))
+        .args(cli.get_arguments().cloned())
+        .subcommands(visible.cloned())
+}
+
+/// Replaces `from` in a generated script, failing when there's nothing to replace
+/// because the generator's output changed shape
+fn patch(shell: Shell, script: String, from: &str, to: &str) -> Result<String> {
+    if !script.contains(from) {
+        let name = shell.to_possible_value().expect("no skipped variants");
+        bail!(
+            "failed to add bookmark completions to the {} script, it has no {from:?}",
+            name.get_name()
+        );
+    }
+    Ok(script.replace(from, to))
+}
+
+/// Bookmark commands along with their aliases, which only some generators list
+/// as subcommands of their own
+fn bookmark_commands() -> Vec<String> {
+    let cli = Cli::command();
+    let mut names = vec![];
+    for name in BOOKMARK_COMMANDS {
+        let sub = cli
+            .find_subcommand(name)
+            .expect("bookmark commands are subcommands");
+        names.push(name.to_string());
+        names.extend(sub.get_all_aliases().map(String::from));
+    }
+    names
+}
+
+fn complete_bookmarks(shell: Shell, script: String) -> Result<String> {
+    let script = match shell {
+        Shell::Bash => {
+            let commands = bookmark_commands().join(" | ");
+            format!(
+                r#"{script}
+_markd_bookmarks() {{
+    if [[ $COMP_CWORD -eq 2 && ${{COMP_WORDS[2]}} != -* ]]; then
+        case "${{COMP_WORDS[1]}}" in
+            {commands})
+                COMPREPLY=($(compgen -W "$(markd _complete 2>/dev/null)" -- "${{COMP_WORDS[2]}}"))
+                return 0
+                ;;
+        esac
+    fi
+    _markd "$@"
+}}
+complete -F _markd_bookmarks -o bashdefault -o default markd
+"#
+            )
+        }
+        // Every positional named `bookmark` gets completed, including the `tag` subcommands
+        Shell::Zsh => {
+            let script = patch(
+                shell,
+                script,
+                ":bookmark:_default'",
+                ":bookmark:_markd_bookmarks'",
+            )?;
+            patch(
+                shell,
+                script,
+                "if [ \"$funcstack[1]\" = \"_markd\" ]",
+                r#"(( $+functions[_markd_bookmarks] )) ||
+_markd_bookmarks() {
+    local -a bookmarks
+    bookmarks=(${(f)"$(markd _complete 2>/dev/null)"})
+    _wanted bookmarks expl bookmark compadd -a bookmarks
+}
+
+if [ "$funcstack[1]" = "_markd" ]"#,
+            )?
+        }
+        Shell::Fish => format!(
+            "{script}complete -c markd -n \"__fish_markd_using_subcommand {}\" -f -a \"(markd _complete 2>/dev/null)\"\n",
+            bookmark_commands().join(" ")
+        ),
+        // Arg completers accept candidates as lines of output
+        Shell::Elvish => BOOKMARK_COMMANDS.iter().try_fold(script, |script, cmd| {
+            patch(
+                shell,
+                script,
+                &format!("&'markd;{cmd}'= {{\n"),
+                &format!("&'markd;{cmd}'= {{\n            markd _complete\n"),
+            )
+        })?,
+        Shell::Xonsh => script,
+        Shell::Nushell => {
+            let script = patch(
+                shell,
+                script,
+                "module completions {\n",
+                "module completions {\n\n  def \"nu-complete markd bookmarks\" [] {\n    ^markd _complete | lines\n  }\n",
+            )?;
+            let script = patch(shell, script, "    bookmark: string\n", BOOKMARK_NU)?;
+            patch(shell, script, "    bookmark?: string\n", BOOKMARK_NU_OPTIONAL)?
+        }
+        Shell::Powershell => BOOKMARK_COMMANDS.iter().try_fold(script, |script, cmd| {
+            patch(
+                shell,
+                script,
+                &format!("'markd;{cmd}' {{\n"),
+                &format!(
+                    "'markd;{cmd}' {{\n            markd _complete 2>$null | ForEach-Object {{ [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_) }}\n"
+                ),
+            )
+        })?,
+    };
+    Ok(script)
+}
+
+pub struct InitOptions {
+    pub cmd: String,
+    pub completions: bool,
+    pub hash_dirs: bool,
+    pub widget: bool,
+    pub track: bool,
+}
+
+/// Prints the integration script meant to be evaluated on shell startup,
+/// e.g. `eval "$(markd init zsh)"`
+pub fn init(shell: Shell, bookmarks: &Bookmarks, opts: InitOptions) -> Result<()> {
+    println!("{}", init_script(shell, bookmarks, opts)?);
+    Ok(())
+}
+
+fn init_script(shell: Shell, bookmarks: &Bookmarks, opts: InitOptions) -> Result<String> {
+    if opts.hash_dirs && !matches!(shell, Shell::Zsh) {
+        bail!("--hash-dirs is only supported for zsh");
+    }
+    let widget = match (opts.widget, shell) {
+        (false, _) => None,
+        (true, Shell::Zsh) => Some(ZSH_WIDGET),
+        (true, Shell::Fish) => Some(FISH_WIDGET),
+        (true, _) => bail!("--widget is only supported for zsh and fish"),
+    };
+    let track = match (opts.track, shell) {
+        (false, _) => None,
+        (true, Shell::Zsh) => Some(ZSH_TRACK),
+        (true, Shell::Bash) => Some(BASH_TRACK),
+        (true, Shell::Fish) => Some(FISH_TRACK),
+        (true, _) => bail!("--track is only supported for zsh, bash and fish"),
+    };
+    // Completions go first, powershell requires `using` statements at the top
+    let mut script = match opts.completions && !matches!(shell, Shell::Xonsh) {
+        true => completion_script(shell)? + "\n",
+        false => String::new(),
+    };
+    script.push_str(&shell.snippet(&opts.cmd));
+    match shell {
+        Shell::Zsh => script.push_str(&format!("\n{ZSH_OLDPWD}")),
+        Shell::Fish => script.push_str(&format!("\n{FISH_OLDPWD}")),
+        _ => {}
+    }
+    if opts.hash_dirs {
+        let bookmarks = resolve::expanded(bookmarks, bookmarks);
+        for (name, entry) in bookmarks.iter().filter(|(name, _)| valid_hash_name(name)) {
+            script.push_str(&format!("\nhash -d {name}={}", quote(&entry.path)));
+        }
+    }
+    if let Some(widget) = widget {
+        script.push('\n');
+        script.push_str(&widget.replace("{cmd}", &opts.cmd));
+    }
+    if let Some(track) = track {
+        script.push('\n');
+        script.push_str(track);
+    }
+    Ok(script)
+}
+
+/// Renders one alias (an abbreviation for fish) per bookmark that changes to its
+/// directory, so bookmarks keep working where markd isn't installed
+pub fn aliases(shell: Shell, bookmarks: &Bookmarks) -> String {
+    let mut script = String::new();
+    for (name, entry) in bookmarks {
+        if !valid_hash_name(name) {
+            script.push_str(&format!("# skipped {name}, not a valid alias name\n"));
+            continue;
+        }
+        let path = &entry.path;
+        let line = match shell {
+            Shell::Zsh | Shell::Bash => {
+                format!("alias {name}={}", quote(&format!("cd {}", quote(path))))
+            }
+            Shell::Fish => format!("abbr -a {name} cd {}", fish_quote(path)),
+            Shell::Powershell => format!(
+                "function {name} {{ Set-Location -LiteralPath '{}' }}",
+                path.replace('\'', "''")
+            ),
+            Shell::Nushell => format!("alias {name} = cd {}", json_quote(path)),
+            Shell::Elvish => format!("fn {name} {{ cd '{}' }}", path.replace('\'', "''")),
+            Shell::Xonsh => format!(
+                "aliases[{}] = ['cd', {}]",
+                json_quote(name),
+                json_quote(path)
+            ),
+        };
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Double-quoted string with JSON escaping, which is valid in YAML, KDL, nushell and python
+pub fn json_quote(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+/// zsh named directories can't contain characters that would end the `~name` word
+fn valid_hash_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Single-quotes `s` for POSIX shells
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn read_rc(rc: &Path) -> Result<String> {
+    match std::fs::read_to_string(rc) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", rc.display())),
+    }
+}
+
+pub fn install(shell: Shell, cmd: &str) -> Result<()> {
+    let rc = shell.rc_file()?;
+    let current = read_rc(&rc)?;
+    if current.contains(BEGIN_MARKER) {
+        println!(
+            "{} shell integration is already installed in {}",
+            "Info:".yellow().bold(),
+            rc.display()
+        );
+        return Ok(());
+    }
+    println!("{}\n", shell.init_line(cmd));
+    if !confirm(&format!(
+        "the above will be appended to {}, would you like to continue?",
+        rc.display()
+    )) {
+        println!("{} installation cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    if let Some(parent) = rc.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+        if let Shell::Nushell = shell {
+            let opts = InitOptions {
+                cmd: cmd.to_string(),
+                completions: true,
+                hash_dirs: false,
+                widget: false,
+                track: false,
+            };
+            let script = init_script(shell, &Bookmarks::new(), opts)?;
+            let path = parent.join("markd.nu");
+            std::fs::write(&path, script + "\n")
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rc)
+        .with_context(|| format!("failed to open {}", rc.display()))?;
+    let separator = match current.as_str() {
+        "" => "",
+        c if c.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    writeln!(
+        file,
+        "{separator}{BEGIN_MARKER}\n{}\n{END_MARKER}",
+        shell.init_line(cmd)
+    )
+    .with_context(|| format!("failed to write to {}", rc.display()))?;
+    println!(
+        "{} shell integration installed in {}, restart your shell to use it",
+        "Success:".green().bold(),
+        rc.display()
+    );
+    Ok(())
+}
+
+/// Removes the block added by `install`, along with the blank line separating it
+pub fn uninstall(shell: Shell) -> Result<()> {
+    let rc = shell.rc_file()?;
+    let current = read_rc(&rc)?;
+    let Some(start) = current.find(BEGIN_MARKER) else {
+        println!(
+            "{} shell integration is not installed in {}",
+            "Info:".yellow().bold(),
+            rc.display()
+        );
+        return Ok(());
+    };
+    let end = current[start..]
+        .find(END_MARKER)
+        .map(|i| start + i + END_MARKER.len())
+        .with_context(|| {
+            format!(
+                "{} has no end marker, remove the markd section by hand",
+                rc.display()
+            )
+        })?;
+    println!("{}\n", &current[start..end]);
+    if !confirm(&format!(
+        "the above will be removed from {}, would you like to continue?",
+        rc.display()
+    )) {
+        println!("{} uninstallation cancelled", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let before = &current[..start];
+    let before = before
+        .strip_suffix('\n')
+        .filter(|b| b.ends_with('\n'))
+        .unwrap_or(before);
+    let after = current[end..].strip_prefix('\n').unwrap_or(&current[end..]);
+    std::fs::write(&rc, format!("{before}{after}"))
+        .with_context(|| format!("failed to write to {}", rc.display()))?;
+    if let (Shell::Nushell, Some(parent)) = (shell, rc.parent()) {
+        match std::fs::remove_file(parent.join("markd.nu")) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err).context("failed to remove markd.nu")
+            }
+            _ => {}
+        }
+    }
+    println!(
+        "{} shell integration removed from {}, restart your shell to apply it",
+        "Success:".green().bold(),
+        rc.display()
+    );
+    Ok(())
+}
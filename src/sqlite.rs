@@ -0,0 +1,146 @@
+//! SQLite storage, for large databases that are slow to parse and rewrite as TOML
+
+use crate::{
+    db::{Bookmark, Bookmarks},
+    storage::Storage,
+};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS bookmarks (
+    name TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '[]',
+    note TEXT,
+    created TEXT,
+    used TEXT,
+    hits INTEGER NOT NULL DEFAULT 0,
+    hosts TEXT NOT NULL DEFAULT '[]'
+)";
+
+/// Columns added after the table was first released: name, definition and the
+/// value to read in their place from databases that don't have them yet
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("archived", "INTEGER NOT NULL DEFAULT 0", "0"),
+    ("aliases", "TEXT NOT NULL DEFAULT '[]'", "'[]'"),
+    ("pinned", "INTEGER NOT NULL DEFAULT 0", "0"),
+    ("expires", "TEXT", "NULL"),
+    ("modified", "TEXT", "NULL"),
+];
+
+/// Opens the database for writing, creating the table and missing columns if needed
+fn open(path: &Path) -> Result<Connection> {
+    let conn =
+        Connection::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    conn.execute_batch(SCHEMA)
+        .with_context(|| format!("failed to set up {}", path.display()))?;
+    let existing = columns(&conn)?;
+    for (column, definition, _) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == column) {
+            conn.execute_batch(&format!(
+                "ALTER TABLE bookmarks ADD COLUMN {column} {definition}"
+            ))
+            .with_context(|| format!("failed to upgrade {}", path.display()))?;
+        }
+    }
+    Ok(conn)
+}
+
+fn columns(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('bookmarks')")?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+    Ok(names.collect::<rusqlite::Result<_>>()?)
+}
+
+pub struct SqliteFile(pub PathBuf);
+
+impl Storage for SqliteFile {
+    fn load(&self) -> Result<Bookmarks> {
+        let path = &self.0;
+        if !path.exists() {
+            return Ok(Bookmarks::new());
+        }
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let tables: i64 = conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'bookmarks'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables == 0 {
+            return Ok(Bookmarks::new());
+        }
+        // Read-only databases can't be upgraded, fall back to defaults for new columns
+        let existing = columns(&conn)?;
+        let added: Vec<_> = ADDED_COLUMNS
+            .iter()
+            .map(
+                |(column, _, default)| match existing.iter().any(|c| c == column) {
+                    true => *column,
+                    false => *default,
+                },
+            )
+            .collect();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name, path, tags, note, created, used, hits, hosts, {} FROM bookmarks",
+            added.join(", ")
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            let tags: String = row.get(2)?;
+            let hosts: String = row.get(7)?;
+            let aliases: String = row.get(9)?;
+            let hits: i64 = row.get(6)?;
+            let bookmark = Bookmark {
+                path: row.get(1)?,
+                tags: serde_json::from_str(&tags).unwrap_or_default(),
+                note: row.get(3)?,
+                created: row.get(4)?,
+                used: row.get(5)?,
+                hits: hits as u64,
+                hosts: serde_json::from_str(&hosts).unwrap_or_default(),
+                archived: row.get(8)?,
+                aliases: serde_json::from_str(&aliases).unwrap_or_default(),
+                pinned: row.get(10)?,
+                expires: row.get(11)?,
+                modified: row.get(12)?,
+            };
+            Ok((row.get(0)?, bookmark))
+        })?;
+        rows.collect::<rusqlite::Result<_>>()
+            .with_context(|| format!("failed to read {}", path.display()))
+    }
+
+    /// Replaces the stored bookmarks in a single transaction
+    fn save(&self, bookmarks: &Bookmarks) -> Result<()> {
+        let path = &self.0;
+        let mut conn = open(path)?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM bookmarks", [])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO bookmarks (name, path, tags, note, created, used, hits, hosts, archived, aliases, pinned, expires, modified)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?;
+            for (name, b) in bookmarks {
+                insert.execute(params![
+                    name,
+                    b.path,
+                    serde_json::to_string(&b.tags)?,
+                    b.note,
+                    b.created,
+                    b.used,
+                    b.hits as i64,
+                    serde_json::to_string(&b.hosts)?,
+                    b.archived,
+                    serde_json::to_string(&b.aliases)?,
+                    b.pinned,
+                    b.expires,
+                    b.modified,
+                ])?;
+            }
+        }
+        tx.commit()
+            .with_context(|| format!("failed to write to {}", path.display()))
+    }
+}
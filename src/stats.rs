@@ -0,0 +1,142 @@
+//! Usage statistics over the whole bookmarks collection.
+
+use crate::{
+    config::Config,
+    db::{Bookmark, Database},
+    new_table, print_table, resolve,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BTreeMap, path::Path};
+
+/// Bookmarks listed in the most visited and least recently used rankings
+const TOP: usize = 5;
+
+#[derive(Serialize)]
+struct Stats<'a> {
+    bookmarks: usize,
+    /// Bookmarks whose directory no longer exists
+    dead: usize,
+    aliases: usize,
+    /// Bookmark count per tag
+    tags: BTreeMap<&'a str, usize>,
+    visits: u64,
+    /// Average number of components in the bookmarked paths
+    average_depth: f64,
+    most_visited: Vec<Usage<'a>>,
+    least_recently_used: Vec<Usage<'a>>,
+}
+
+#[derive(Serialize)]
+struct Usage<'a> {
+    name: &'a str,
+    hits: u64,
+    used: Option<DateTime<Utc>>,
+}
+
+impl<'a> Usage<'a> {
+    fn new(name: &'a str, bookmark: &Bookmark) -> Self {
+        Self {
+            name,
+            hits: bookmark.hits,
+            used: bookmark.used,
+        }
+    }
+}
+
+impl<'a> Stats<'a> {
+    fn collect(db: &'a Database) -> Self {
+        let entries: Vec<_> = db.bookmarks.iter().collect();
+        let targets: Vec<_> = entries
+            .iter()
+            .map(|(_, b)| resolve::target(&db.bookmarks, b))
+            .collect();
+        let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+        for tag in entries.iter().flat_map(|(_, b)| &b.tags) {
+            *tags.entry(tag).or_default() += 1;
+        }
+        let depth: usize = targets
+            .iter()
+            .map(|path| Path::new(path.as_ref()).components().count())
+            .sum();
+        let mut most_visited: Vec<_> = entries.iter().filter(|(_, b)| b.hits > 0).collect();
+        most_visited.sort_by_key(|(_, b)| Reverse(b.hits));
+        // Never used sorts first, as the least recent of all
+        let mut least_recent = entries.clone();
+        least_recent.sort_by_key(|(_, b)| b.used);
+        Self {
+            bookmarks: entries.len(),
+            dead: targets
+                .iter()
+                .filter(|path| !Path::new(path.as_ref()).is_dir())
+                .count(),
+            aliases: entries.iter().map(|(_, b)| b.aliases.len()).sum(),
+            tags,
+            visits: entries.iter().map(|(_, b)| b.hits).sum(),
+            average_depth: match entries.len() {
+                0 => 0.0,
+                n => depth as f64 / n as f64,
+            },
+            most_visited: most_visited
+                .into_iter()
+                .take(TOP)
+                .map(|(name, b)| Usage::new(name, b))
+                .collect(),
+            least_recently_used: least_recent
+                .into_iter()
+                .take(TOP)
+                .map(|(name, b)| Usage::new(name, b))
+                .collect(),
+        }
+    }
+}
+
+pub fn stats(db: &Database, config: &Config, json: bool) -> Result<()> {
+    let stats = Stats::collect(db);
+    if json {
+        let json = serde_json::to_string_pretty(&stats).context("failed to serialize stats")?;
+        println!("{json}");
+        return Ok(());
+    }
+    if stats.bookmarks == 0 {
+        println!("{} no bookmarks yet", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let tags = stats
+        .tags
+        .iter()
+        .map(|(tag, count)| format!("{tag} ({count})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut table = new_table(["Statistic", "Value"]);
+    table.push_record(["Bookmarks".into(), stats.bookmarks.to_string()]);
+    table.push_record(["Dead paths".into(), stats.dead.to_string()]);
+    table.push_record(["Aliases".into(), stats.aliases.to_string()]);
+    table.push_record(["Tags".into(), tags]);
+    table.push_record(["Visits".into(), stats.visits.to_string()]);
+    table.push_record([
+        "Average path depth".into(),
+        format!("{:.1}", stats.average_depth),
+    ]);
+    println!("{}", "Totals:".green().bold());
+    print_table(table);
+
+    let used = |u: &Usage| u.used.map_or("never".into(), |t| config.format_time(t));
+    if !stats.most_visited.is_empty() {
+        println!("{}", "Most visited:".green().bold());
+        let mut table = new_table(["Name", "Visits", "Last used"]);
+        for u in &stats.most_visited {
+            table.push_record([u.name.into(), u.hits.to_string(), used(u)]);
+        }
+        print_table(table);
+    }
+    println!("{}", "Least recently used:".green().bold());
+    let mut table = new_table(["Name", "Last used", "Visits"]);
+    for u in &stats.least_recently_used {
+        table.push_record([u.name.into(), used(u), u.hits.to_string()]);
+    }
+    print_table(table);
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Backends the bookmarks database can be persisted with. Commands only deal with
+//! `Database`, backends turn its entries into their own on-disk representation.
+
+use crate::db::{Bookmark, Bookmarks};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+pub trait Storage {
+    /// Every stored entry, including the ones scoped to other hosts. Never creates
+    /// or modifies anything, a missing database has no entries.
+    fn load(&self) -> Result<Bookmarks>;
+    fn save(&self, bookmarks: &Bookmarks) -> Result<()>;
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Toml,
+    Json,
+    /// Needs the `sqlite` feature
+    Sqlite,
+    /// Starts out empty and forgets every change once markd exits
+    Memory,
+}
+
+impl Backend {
+    /// Picks the backend from the file extension, TOML unless it's obviously something else
+    pub fn detect(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Backend::Json,
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            _ => Backend::Toml,
+        }
+    }
+
+    pub fn open(self, path: &Path) -> Result<Box<dyn Storage>> {
+        let path = path.to_path_buf();
+        Ok(match self {
+            Backend::Toml => Box::new(TomlFile(path)),
+            Backend::Json => Box::new(JsonFile(path)),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => Box::new(crate::sqlite::SqliteFile(path)),
+            #[cfg(not(feature = "sqlite"))]
+            Backend::Sqlite => anyhow::bail!(
+                "{} is a SQLite database, but markd was built without the `sqlite` feature",
+                path.display()
+            ),
+            Backend::Memory => Box::<Memory>::default(),
+        })
+    }
+}
+
+/// Layout shared by the TOML and JSON files, every entry under a `bookmarks` key
+#[derive(Deserialize)]
+struct Stored {
+    #[serde(default, deserialize_with = "bookmarks_or_paths")]
+    bookmarks: Bookmarks,
+}
+
+/// Borrowing counterpart of `Stored`, serializes to the same layout
+fn stored(bookmarks: &Bookmarks) -> BTreeMap<&str, &Bookmarks> {
+    BTreeMap::from([("bookmarks", bookmarks)])
+}
+
+/// Entries can also be written by hand as a bare `name = "path"` pair
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Path(String),
+    Full(Bookmark),
+}
+
+fn bookmarks_or_paths<'de, D: Deserializer<'de>>(de: D) -> Result<Bookmarks, D::Error> {
+    let entries = BTreeMap::<String, Entry>::deserialize(de)?;
+    let bookmarks = entries.into_iter().map(|(name, entry)| {
+        let bookmark = match entry {
+            Entry::Path(path) => Bookmark {
+                created: None,
+                ..Bookmark::new(path)
+            },
+            Entry::Full(bookmark) => bookmark,
+        };
+        (name, bookmark)
+    });
+    Ok(bookmarks.collect())
+}
+
+pub struct TomlFile(pub PathBuf);
+
+impl Storage for TomlFile {
+    fn load(&self) -> Result<Bookmarks> {
+        let path = &self.0;
+        let raw = match std::fs::read_to_string(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Bookmarks::new()),
+            raw => raw.with_context(|| format!("failed to read {}", path.display()))?,
+        };
+        parse_toml(&raw).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn save(&self, bookmarks: &Bookmarks) -> Result<()> {
+        let toml = toml::to_string(&stored(bookmarks)).context("failed to serialize data")?;
+        write_atomic(&self.0, toml.as_bytes())
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it over the original,
+/// so a crash mid-write never leaves a truncated file behind. A symlink is
+/// followed and the file it points to replaced, keeping that file's permissions.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let path = &follow_links(path);
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} has no file name", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    let permissions = std::fs::metadata(path).ok().map(|m| m.permissions());
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&tmp)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()
+    };
+    write().with_context(|| format!("failed to write to {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Where a chain of symlinks ends, even if the file there doesn't exist yet
+fn follow_links(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // Bounded, so a symlink loop can't hang
+    for _ in 0..40 {
+        let Ok(target) = std::fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+    path
+}
+
+/// Reads a TOML bookmarks file without ever creating or modifying it
+pub fn read_toml(path: &Path) -> Result<Bookmarks> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_toml(&raw).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Reads both the current format and the legacy flat `name = "path"` one,
+/// which gets upgraded the next time the file is saved
+pub fn parse_toml(raw: &str) -> Result<Bookmarks> {
+    let table: toml::Table = toml::from_str(raw)?;
+    match table.get("bookmarks").is_some_and(toml::Value::is_table) {
+        true => Ok(Stored::deserialize(table)?.bookmarks),
+        false => Ok(bookmarks_or_paths(table)?),
+    }
+}
+
+pub fn parse_json(raw: &str) -> Result<Bookmarks> {
+    Ok(serde_json::from_str::<Stored>(raw)?.bookmarks)
+}
+
+pub struct JsonFile(pub PathBuf);
+
+impl Storage for JsonFile {
+    fn load(&self) -> Result<Bookmarks> {
+        let path = &self.0;
+        let raw = match std::fs::read_to_string(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Bookmarks::new()),
+            raw => raw.with_context(|| format!("failed to read {}", path.display()))?,
+        };
+        parse_json(&raw).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn save(&self, bookmarks: &Bookmarks) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(&stored(bookmarks)).context("failed to serialize data")?;
+        write_atomic(&self.0, json.as_bytes())
+    }
+}
+
+#[derive(Default)]
+pub struct Memory(RefCell<Bookmarks>);
+
+impl Storage for Memory {
+    fn load(&self) -> Result<Bookmarks> {
+        Ok(self.0.borrow().clone())
+    }
+
+    fn save(&self, bookmarks: &Bookmarks) -> Result<()> {
+        *self.0.borrow_mut() = bookmarks.clone();
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Syncing the bookmarks through a git repository. Entries are merged three-way
+//! against the last synced version, so edits on different machines never end up
+//! as textual conflicts.
+
+use crate::{
+    config::Config,
+    db::{self, Bookmarks, Database},
+    storage::{parse_toml, Storage, TomlFile},
+};
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Name of the bookmarks file inside the sync repository
+const FILE: &str = "bookmarks.toml";
+
+pub fn sync(db: &mut Database, config: &Config, repo: Option<PathBuf>) -> Result<()> {
+    let repo = repo
+        .or_else(|| config.sync_repo.clone())
+        .context("no sync repository, set sync_repo in the config or pass --repo")?;
+    if git(&repo, &["rev-parse", "--git-dir"]).is_err() {
+        bail!(
+            "{} is not a git repository, clone or `git init` one first",
+            repo.display()
+        );
+    }
+    let upstream = git(&repo, &["rev-parse", "--abbrev-ref", "@{u}"]).is_ok();
+    if upstream {
+        git(&repo, &["fetch", "--quiet"])?;
+    }
+    let base = committed(&repo, "HEAD")?;
+    let theirs = match upstream {
+        true => committed(&repo, "@{u}")?,
+        false => base.clone(),
+    };
+    let (merged, conflicts) = three_way(&base, &db.own(), &theirs);
+    for (name, kept, dropped) in &conflicts {
+        println!(
+            "{} {} was changed on both sides, kept {} over {}",
+            "Info:".yellow().bold(),
+            name.magenta(),
+            kept.green(),
+            dropped.red()
+        );
+    }
+
+    // Upstream is merged first so the commit below can be pushed, its copy of the
+    // bookmarks file is replaced by the merged entries right after anyway
+    if upstream {
+        let merge = git(
+            &repo,
+            &["merge", "--quiet", "--no-edit", "-X", "ours", "@{u}"],
+        );
+        if let Err(err) = merge {
+            let _ = git(&repo, &["merge", "--abort"]);
+            return Err(err.context("failed to merge the remote changes"));
+        }
+    }
+    TomlFile(repo.join(FILE)).save(&merged)?;
+    git(&repo, &["add", FILE])?;
+    let committed = match git(&repo, &["diff", "--cached", "--quiet"]) {
+        Ok(_) => false,
+        Err(_) => {
+            let host =
+                hostname::get().map_or("unknown host".into(), |h| h.to_string_lossy().to_string());
+            let message = format!("Sync bookmarks from {host}");
+            git(&repo, &["commit", "--quiet", "-m", &message])?;
+            true
+        }
+    };
+    // A fresh clone of an empty repository has a remote but no upstream yet
+    let remote = git(&repo, &["remote"])?;
+    let pushed = match (upstream, remote.lines().next()) {
+        (true, _) => git(&repo, &["push", "--quiet"]).map(|_| true)?,
+        (false, Some(remote)) if committed => {
+            git(&repo, &["push", "--quiet", "-u", remote, "HEAD"]).map(|_| true)?
+        }
+        _ => false,
+    };
+
+    let changed = merged != db.own();
+    if changed {
+        db.replace_own(merged);
+        db::save(db)?;
+    }
+    let msg = match (committed, changed) {
+        (false, false) => "bookmarks already in sync",
+        (true, false) if pushed => "pushed local changes",
+        (true, false) => "committed local changes",
+        (false, true) => "pulled remote changes",
+        (true, true) => "pulled remote changes and pushed local ones",
+    };
+    println!("{} {msg}", "Success:".green().bold());
+    Ok(())
+}
+
+/// Merges entries changed on either side since `base`. An entry changed on both sides
+/// keeps our version unless ours was removed, combining usage when both still point to
+/// the same directory. Returns the merged entries and the conflicting names with the
+/// kept and dropped paths.
+fn three_way(
+    base: &Bookmarks,
+    ours: &Bookmarks,
+    theirs: &Bookmarks,
+) -> (Bookmarks, Vec<(String, String, String)>) {
+    let mut merged = Bookmarks::new();
+    let mut conflicts = vec![];
+    let names = base.keys().chain(ours.keys()).chain(theirs.keys());
+    for name in names {
+        if merged.contains_key(name) {
+            continue;
+        }
+        let (b, o, t) = (base.get(name), ours.get(name), theirs.get(name));
+        let entry = match (o, t) {
+            _ if o == b => t,
+            _ if t == b || o == t => o,
+            (Some(o), Some(t)) if o.path == t.path => {
+                let mut entry = o.clone();
+                entry.absorb(t);
+                merged.insert(name.clone(), entry);
+                continue;
+            }
+            (Some(o), Some(t)) => {
+                conflicts.push((name.clone(), o.path.clone(), t.path.clone()));
+                Some(o)
+            }
+            // Removed on one side and edited on the other, the edit wins
+            (o, t) => o.or(t),
+        };
+        if let Some(entry) = entry {
+            merged.insert(name.clone(), entry.clone());
+        }
+    }
+    (merged, conflicts)
+}
+
+/// The bookmarks file as of `rev`, empty if it didn't exist yet
+fn committed(repo: &Path, rev: &str) -> Result<Bookmarks> {
+    match git(repo, &["show", &format!("{rev}:{FILE}")]) {
+        Ok(raw) => parse_toml(&raw).with_context(|| format!("failed to parse {FILE} at {rev}")),
+        Err(_) => Ok(Bookmarks::new()),
+    }
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Bookmark;
+
+    fn bookmarks(entries: &[(&str, &Bookmark)]) -> Bookmarks {
+        entries
+            .iter()
+            .map(|(name, b)| (name.to_string(), (*b).clone()))
+            .collect()
+    }
+
+    #[test]
+    fn takes_changes_from_either_side() {
+        let a = Bookmark::new("/a".into());
+        let b = Bookmark::new("/b".into());
+        let tagged = Bookmark {
+            tags: vec!["work".into()],
+            ..a.clone()
+        };
+        let base = bookmarks(&[("a", &a), ("b", &b)]);
+        let ours = bookmarks(&[("a", &tagged), ("b", &b), ("new", &b)]);
+        let theirs = bookmarks(&[("a", &a)]);
+        let (merged, conflicts) = three_way(&base, &ours, &theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged["a"].tags, ["work"]);
+        assert!(merged.contains_key("new"));
+        assert!(!merged.contains_key("b"), "removed on their side");
+    }
+
+    #[test]
+    fn edit_beats_removal() {
+        let a = Bookmark::new("/a".into());
+        let moved = Bookmark::new("/moved".into());
+        let base = bookmarks(&[("a", &a)]);
+        let (merged, _) = three_way(&base, &Bookmarks::new(), &bookmarks(&[("a", &moved)]));
+        assert_eq!(merged["a"].path, "/moved");
+        let (merged, _) = three_way(&base, &bookmarks(&[("a", &moved)]), &Bookmarks::new());
+        assert_eq!(merged["a"].path, "/moved");
+    }
+
+    #[test]
+    fn same_directory_combines_both_edits() {
+        let a = Bookmark::new("/a".into());
+        let ours = Bookmark {
+            tags: vec!["ours".into()],
+            ..a.clone()
+        };
+        let theirs = Bookmark {
+            hits: 5,
+            tags: vec!["theirs".into()],
+            ..a.clone()
+        };
+        let (merged, conflicts) = three_way(
+            &bookmarks(&[("a", &a)]),
+            &bookmarks(&[("a", &ours)]),
+            &bookmarks(&[("a", &theirs)]),
+        );
+        assert!(conflicts.is_empty());
+        assert_eq!(merged["a"].tags, ["ours", "theirs"]);
+        assert_eq!(merged["a"].hits, 5);
+    }
+
+    #[test]
+    fn diverging_paths_keep_ours_and_report() {
+        let a = Bookmark::new("/a".into());
+        let (merged, conflicts) = three_way(
+            &bookmarks(&[("a", &a)]),
+            &bookmarks(&[("a", &Bookmark::new("/ours".into()))]),
+            &bookmarks(&[("a", &Bookmark::new("/theirs".into()))]),
+        );
+        assert_eq!(merged["a"].path, "/ours");
+        assert_eq!(
+            conflicts,
+            [("a".to_string(), "/ours".to_string(), "/theirs".to_string())]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Directories visited with `cd`, recorded by the `markd init --track` shell hook.
+//! They never show up as bookmarks, but break ties when a query matches several.
+
+use crate::{db, storage};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::ErrorKind, path::Path, path::PathBuf};
+
+/// Directories kept before the least frecent ones are forgotten
+const MAX_DIRS: usize = 1000;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Tracked {
+    #[serde(default)]
+    dirs: BTreeMap<String, Visits>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Visits {
+    hits: u64,
+    used: DateTime<Utc>,
+}
+
+/// `markd/tracked.toml` in the state directory
+fn path() -> PathBuf {
+    db::state_home().join("tracked.toml")
+}
+
+impl Tracked {
+    pub fn load() -> Result<Self> {
+        let path = path();
+        match std::fs::read_to_string(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Tracked::default()),
+            raw => {
+                let raw = raw.with_context(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&raw).with_context(|| format!("failed to parse {}", path.display()))
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let toml = toml::to_string(self).context("failed to serialize tracked directories")?;
+        storage::write_atomic(&path, toml.as_bytes())
+    }
+
+    /// How often and how recently `dir` was visited, 0 if it never was
+    pub fn frecency(&self, dir: &str) -> f64 {
+        let dir = dir.trim_end_matches('/');
+        self.dirs
+            .get(dir)
+            .map_or(0.0, |v| db::frecency(v.hits, v.used, db::now()))
+    }
+}
+
+/// Counts a visit to `dir`, called by the shell hook on every directory change, so
+/// it stays quiet and skips the bookmarks database altogether
+pub fn track(dir: &Path) -> Result<()> {
+    if !dir.is_absolute() {
+        bail!("{} is not an absolute path", dir.display());
+    }
+    let mut tracked = Tracked::load()?;
+    let dir = dir.to_string_lossy();
+    let dir = match dir.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    };
+    let now = db::now();
+    let visits = tracked
+        .dirs
+        .entry(dir.to_string())
+        .or_insert(Visits { hits: 0, used: now });
+    visits.hits += 1;
+    visits.used = now;
+    if tracked.dirs.len() > MAX_DIRS {
+        let mut ranked: Vec<_> = tracked
+            .dirs
+            .iter()
+            .map(|(dir, v)| (db::frecency(v.hits, v.used, now), dir.clone()))
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, dir) in ranked.into_iter().take(tracked.dirs.len() - MAX_DIRS) {
+            tracked.dirs.remove(&dir);
+        }
+    }
+    tracked.save()
+}
@@ -0,0 +1,177 @@
+//! Removed and overwritten bookmarks, kept in `<database>.trash` for `trash_days`
+//! so `markd undo` and `markd trash restore` can bring them back.
+
+use crate::{
+    config::Config,
+    db::{self, Bookmark, Database},
+    new_table, print_table, storage,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf};
+
+#[derive(Serialize, Deserialize)]
+pub struct Trashed {
+    pub name: String,
+    /// Entries trashed by the same command share this timestamp
+    pub removed: DateTime<Utc>,
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+}
+
+#[derive(Deserialize, Default)]
+struct TrashFile {
+    #[serde(default)]
+    trash: Vec<Trashed>,
+}
+
+pub struct Trash {
+    path: PathBuf,
+    /// Oldest first
+    entries: Vec<Trashed>,
+}
+
+impl Trash {
+    /// Reads the trash of a database, forgetting entries older than `keep_days`
+    pub fn open(db: &Database, keep_days: u32) -> Result<Self> {
+        let path = db::sibling(db.file().unwrap_or(db::path()), "trash");
+        let file: TrashFile = match std::fs::read_to_string(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => TrashFile::default(),
+            raw => {
+                let raw = raw.with_context(|| format!("failed to read {}", path.display()))?;
+                toml::from_str(&raw)
+                    .with_context(|| format!("failed to parse {}", path.display()))?
+            }
+        };
+        let cutoff = db::now().checked_sub_signed(Duration::days(keep_days.into()));
+        let mut entries = file.trash;
+        entries.retain(|t| cutoff.is_none_or(|cutoff| t.removed > cutoff));
+        entries.sort_by_key(|t| t.removed);
+        Ok(Self { path, entries })
+    }
+
+    pub fn put(&mut self, entries: impl IntoIterator<Item = (String, Bookmark)>) {
+        // Full precision, so entries trashed by separate commands are never grouped
+        let removed = Utc::now();
+        self.entries
+            .extend(entries.into_iter().map(|(name, bookmark)| Trashed {
+                name,
+                removed,
+                bookmark,
+            }));
+    }
+
+    /// Last time each name was trashed
+    pub fn removals(&self) -> BTreeMap<&str, DateTime<Utc>> {
+        // Oldest first, so later removals of a name overwrite earlier ones
+        self.entries
+            .iter()
+            .map(|t| (t.name.as_str(), t.removed))
+            .collect()
+    }
+
+    /// Takes out every entry trashed by the most recent command
+    fn take_last(&mut self) -> Vec<Trashed> {
+        let Some(last) = self.entries.last().map(|t| t.removed) else {
+            return vec![];
+        };
+        let split = self.entries.partition_point(|t| t.removed < last);
+        self.entries.split_off(split)
+    }
+
+    /// Takes out the most recently trashed entry with the given name
+    fn take(&mut self, name: &str) -> Option<Trashed> {
+        let i = self.entries.iter().rposition(|t| t.name == name)?;
+        Some(self.entries.remove(i))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.entries.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    Err(err).with_context(|| format!("failed to remove {}", self.path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        let file = BTreeMap::from([("trash", &self.entries)]);
+        let toml = toml::to_string(&file).context("failed to serialize trash")?;
+        storage::write_atomic(&self.path, toml.as_bytes())
+    }
+}
+
+/// Moves entries that are about to be removed or overwritten to the trash
+pub fn put(
+    db: &Database,
+    keep_days: u32,
+    entries: impl IntoIterator<Item = (String, Bookmark)>,
+) -> Result<()> {
+    let mut trash = Trash::open(db, keep_days)?;
+    trash.put(entries);
+    trash.save()
+}
+
+pub fn list(db: &Database, config: &Config) -> Result<()> {
+    let trash = Trash::open(db, config.trash_days)?;
+    if trash.entries.is_empty() {
+        println!("{} trash is empty", "Info:".yellow().bold());
+        return Ok(());
+    }
+    let mut table = new_table(["Name", "Path", "Removed"]);
+    for t in trash.entries.iter().rev() {
+        let removed = config.format_time(t.removed);
+        table.push_record([t.name.clone(), t.bookmark.path.clone(), removed]);
+    }
+    print_table(table);
+    Ok(())
+}
+
+/// Brings back the bookmarks removed or overwritten by the last destructive command.
+/// Entries that took their names since are moved to the trash in turn, so undoing
+/// again swaps them back.
+pub fn undo(db: &mut Database, keep_days: u32) -> Result<()> {
+    let mut trash = Trash::open(db, keep_days)?;
+    let restored = trash.take_last();
+    if restored.is_empty() {
+        bail!("nothing to undo");
+    }
+    let mut table = new_table(["Name", "Path"]);
+    let mut displaced = vec![];
+    for t in restored {
+        table.push_record([t.name.clone(), t.bookmark.path.clone()]);
+        if let Some(current) = db.bookmarks.insert(t.name.clone(), t.bookmark) {
+            displaced.push((t.name, current));
+        }
+    }
+    let moved: Vec<_> = displaced.iter().map(|(name, _)| name.clone()).collect();
+    trash.put(displaced);
+    db::save(db)?;
+    trash.save()?;
+    println!("{}", "Restored bookmarks:".green().bold());
+    print_table(table);
+    if !moved.is_empty() {
+        println!(
+            "{} moved the entries they replaced to the trash: {}",
+            "Info:".yellow().bold(),
+            moved.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub fn restore(db: &mut Database, keep_days: u32, name: &str) -> Result<()> {
+    if db.bookmarks.contains_key(name) {
+        bail!("{name} already exists in bookmarks");
+    }
+    let mut trash = Trash::open(db, keep_days)?;
+    let t = trash
+        .take(name)
+        .with_context(|| format!("{name} is not in the trash"))?;
+    db.bookmarks.insert(t.name, t.bookmark);
+    db::save(db)?;
+    trash.save()?;
+    println!("{} {} restored", "Success:".green().bold(), name.magenta());
+    Ok(())
+}
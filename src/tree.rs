@@ -0,0 +1,78 @@
+use crate::{
+    db::{Bookmark, Bookmarks},
+    resolve::target,
+};
+use colored::Colorize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    /// Bookmarks pointing to this directory
+    names: Vec<String>,
+}
+
+/// Renders bookmarks as a directory tree. Directories on the way that aren't
+/// bookmarked and have a single child are collapsed into one line.
+pub fn render<'a>(
+    bookmarks: &Bookmarks,
+    entries: impl Iterator<Item = (&'a String, &'a Bookmark)>,
+    ascii: bool,
+) -> String {
+    let mut root = Node::default();
+    for (name, b) in entries {
+        let path = target(bookmarks, b);
+        let mut node = &mut root;
+        for part in Path::new(path.as_ref()).components() {
+            let part = part.as_os_str().to_string_lossy().to_string();
+            node = node.children.entry(part).or_default();
+        }
+        node.names.push(name.clone());
+    }
+    let mut out = String::new();
+    for (label, node) in &root.children {
+        write_node(&mut out, label, node, "", None, ascii);
+    }
+    out
+}
+
+/// `last` is `None` for top level nodes, which aren't drawn with branches
+fn write_node(
+    out: &mut String,
+    label: &str,
+    node: &Node,
+    prefix: &str,
+    last: Option<bool>,
+    ascii: bool,
+) {
+    let mut label = PathBuf::from(label);
+    let mut node = node;
+    while node.names.is_empty() && node.children.len() == 1 {
+        let (child, next) = node.children.iter().next().expect("one child");
+        label.push(child);
+        node = next;
+    }
+    let (branch, end, pipe) = match ascii {
+        true => ("|-- ", "`-- ", "|   "),
+        false => ("├── ", "└── ", "│   "),
+    };
+    let (connector, indent) = match last {
+        None => ("", ""),
+        Some(false) => (branch, pipe),
+        Some(true) => (end, "    "),
+    };
+    let _ = write!(out, "{prefix}{connector}{}", label.display());
+    if !node.names.is_empty() {
+        let _ = write!(out, "  {}", node.names.join(", ").magenta());
+    }
+    out.push('\n');
+    let prefix = format!("{prefix}{indent}");
+    let count = node.children.len();
+    for (i, (child, next)) in node.children.iter().enumerate() {
+        write_node(out, child, next, &prefix, Some(i + 1 == count), ascii);
+    }
+}
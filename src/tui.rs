@@ -0,0 +1,300 @@
+use crate::{
+    db::{self, parse_tag, Database},
+    trash,
+};
+use anyhow::{Context, Result};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+use std::io::{self, Stderr};
+
+enum Mode {
+    Browse,
+    Search,
+    Rename(String),
+    Tag(String),
+    ConfirmDelete,
+}
+
+struct App<'a> {
+    db: &'a mut Database,
+    query: String,
+    /// Names matching the current query, in display order
+    visible: Vec<String>,
+    state: TableState,
+    mode: Mode,
+    status: String,
+    /// Days deleted bookmarks stay in the trash
+    trash_days: u32,
+}
+
+/// Runs the interactive bookmark manager. The UI is drawn on stderr so the
+/// path selected with Enter can be printed to stdout for `cd $(markd tui)`.
+pub fn run(db: &mut Database, trash_days: u32) -> Result<()> {
+    let mut app = App::new(db, trash_days);
+    let mut terminal = setup()?;
+    let result = app.event_loop(&mut terminal);
+    restore()?;
+    if let Some(path) = result? {
+        print!("{path}");
+    }
+    Ok(())
+}
+
+fn setup() -> Result<Terminal<CrosstermBackend<Stderr>>> {
+    terminal::enable_raw_mode().context("failed to enable raw mode")?;
+    execute!(io::stderr(), EnterAlternateScreen).context("failed to enter alternate screen")?;
+    Terminal::new(CrosstermBackend::new(io::stderr())).context("failed to set up terminal")
+}
+
+fn restore() -> Result<()> {
+    execute!(io::stderr(), LeaveAlternateScreen).context("failed to leave alternate screen")?;
+    terminal::disable_raw_mode().context("failed to disable raw mode")
+}
+
+impl<'a> App<'a> {
+    fn new(db: &'a mut Database, trash_days: u32) -> Self {
+        let mut app = Self {
+            db,
+            query: String::new(),
+            visible: vec![],
+            state: TableState::default(),
+            mode: Mode::Browse,
+            status: String::new(),
+            trash_days,
+        };
+        app.refresh();
+        app
+    }
+
+    fn refresh(&mut self) {
+        let query = self.query.to_lowercase();
+        self.visible = self
+            .db
+            .bookmarks
+            .iter()
+            .filter(|(name, b)| name.contains(&query) || b.path.to_lowercase().contains(&query))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let selected = match self.visible.len() {
+            0 => None,
+            len => Some(self.state.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.state.select(selected);
+    }
+
+    fn selected(&self) -> Option<&String> {
+        self.visible.get(self.state.selected()?)
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    ) -> Result<Option<String>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                return Ok(None);
+            }
+            if let Some(exit) = self.handle_key(key)? {
+                return Ok(exit);
+            }
+        }
+    }
+
+    /// Returns `Some` when the app should exit, holding the path to print if any
+    fn handle_key(&mut self, key: KeyEvent) -> Result<Option<Option<String>>> {
+        match &mut self.mode {
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Some(None)),
+                KeyCode::Enter => {
+                    let path = self
+                        .selected()
+                        .map(|name| self.db.bookmarks[name].path.clone());
+                    return Ok(path.map(Some));
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+                KeyCode::Home | KeyCode::Char('g') => self.state.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.state.select_last(),
+                KeyCode::Char('/') => self.mode = Mode::Search,
+                KeyCode::Char('r') => {
+                    if let Some(name) = self.selected().cloned() {
+                        self.mode = Mode::Rename(name);
+                    }
+                }
+                KeyCode::Char('t') => {
+                    if let Some(name) = self.selected() {
+                        let tags = self.db.bookmarks[name].tags.join(",");
+                        self.mode = Mode::Tag(tags);
+                    }
+                }
+                KeyCode::Char('d') if self.selected().is_some() => {
+                    self.mode = Mode::ConfirmDelete;
+                }
+                _ => {}
+            },
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc | KeyCode::Down | KeyCode::Up => {
+                        self.mode = Mode::Browse
+                    }
+                    KeyCode::Backspace => {
+                        self.query.pop();
+                    }
+                    KeyCode::Char(c) => self.query.push(c),
+                    _ => {}
+                }
+                self.refresh();
+            }
+            Mode::Rename(input) | Mode::Tag(input) => match key.code {
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => {
+                    let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+                    match mode {
+                        Mode::Rename(new_name) => self.rename(new_name)?,
+                        Mode::Tag(tags) => self.retag(&tags)?,
+                        _ => unreachable!(),
+                    }
+                }
+                _ => {}
+            },
+            Mode::ConfirmDelete => {
+                if key.code == KeyCode::Char('y') {
+                    self.delete()?;
+                }
+                self.mode = Mode::Browse;
+            }
+        }
+        Ok(None)
+    }
+
+    fn rename(&mut self, new_name: String) -> Result<()> {
+        let Some(old_name) = self.selected().cloned() else {
+            return Ok(());
+        };
+        let new_name = new_name.trim().to_lowercase();
+        if new_name.is_empty() || new_name == old_name {
+            return Ok(());
+        }
+        if self.db.bookmarks.contains_key(&new_name) {
+            self.status = format!("{new_name} already exists in bookmarks");
+            return Ok(());
+        }
+        let entry = self
+            .db
+            .bookmarks
+            .remove(&old_name)
+            .expect("selected exists");
+        self.db.bookmarks.insert(new_name.clone(), entry);
+        db::save(self.db)?;
+        self.status = format!("renamed {old_name} to {new_name}");
+        self.refresh();
+        if let Some(i) = self.visible.iter().position(|n| *n == new_name) {
+            self.state.select(Some(i));
+        }
+        Ok(())
+    }
+
+    fn retag(&mut self, input: &str) -> Result<()> {
+        let Some(name) = self.selected().cloned() else {
+            return Ok(());
+        };
+        let mut tags: Vec<_> = input.split(',').filter_map(|t| parse_tag(t).ok()).collect();
+        tags.sort();
+        tags.dedup();
+        self.db
+            .bookmarks
+            .get_mut(&name)
+            .expect("selected exists")
+            .tags = tags;
+        db::save(self.db)?;
+        self.status = format!("updated tags of {name}");
+        Ok(())
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        let Some(name) = self.selected().cloned() else {
+            return Ok(());
+        };
+        if let Some(entry) = self.db.bookmarks.remove(&name) {
+            trash::put(self.db, self.trash_days, [(name.clone(), entry)])?;
+        }
+        db::save(self.db)?;
+        self.status = format!("{name} removed from bookmarks");
+        self.refresh();
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search, body, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let search_style = match self.mode {
+            Mode::Search => Style::new().yellow(),
+            _ => Style::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(self.query.as_str())
+                .style(search_style)
+                .block(Block::new().borders(Borders::ALL).title(" Search ")),
+            search,
+        );
+
+        let rows = self.visible.iter().map(|name| {
+            let b = &self.db.bookmarks[name];
+            Row::new([name.clone(), b.path.clone(), b.tags.join(", ")])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(20),
+                Constraint::Percentage(60),
+                Constraint::Percentage(20),
+            ],
+        )
+        .header(Row::new(["Name", "Path", "Tags"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::new().borders(Borders::ALL).title(" Bookmarks "));
+        frame.render_stateful_widget(table, body, &mut self.state);
+
+        let footer_text = match &self.mode {
+            Mode::Browse if !self.status.is_empty() => Line::from(self.status.as_str()),
+            Mode::Browse => {
+                Line::from("enter: cd  /: search  r: rename  t: tags  d: delete  q: quit").dim()
+            }
+            Mode::Search => Line::from("type to search, enter/esc: done").dim(),
+            Mode::Rename(input) => Line::from(format!("new name: {input}")).yellow(),
+            Mode::Tag(input) => Line::from(format!("tags (comma separated): {input}")).yellow(),
+            Mode::ConfirmDelete => Line::from("delete selected bookmark? (y/n)").red(),
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+        if matches!(self.mode, Mode::Browse) {
+            self.status.clear();
+        }
+    }
+}